@@ -1,20 +1,38 @@
+use divan::counter::ItemsCount;
 use divan::Bencher;
 
-use chusst_gen::eval::{Game, GameHistory, SilentSearchFeedback};
+use chusst_gen::eval::{
+    Game, GameHistory, SearchLimits, SearchOptions, SilentSearchFeedback, TranspositionTable,
+};
 use chusst_gen::game::BitboardGame;
 
-#[divan::bench]
-fn search(bench: Bencher) {
-    bench.bench_local(|| {
+// With and without principal variation search
+#[divan::bench(args = [false, true])]
+fn search(bench: Bencher, principal_variation_search: bool) {
+    let options = SearchOptions {
+        principal_variation_search,
+        ..Default::default()
+    };
+    let search = || {
         let game = BitboardGame::new();
         let history = GameHistory::new();
 
         let best_branch = game
-            .get_best_move_recursive(&history, 4, &mut (), &mut SilentSearchFeedback::default())
+            .get_best_move_recursive(
+                &SearchLimits::from_depth(4),
+                &options,
+                &history,
+                &TranspositionTable::default(),
+                &mut (),
+                &mut SilentSearchFeedback::default(),
+            )
             .unwrap();
 
         best_branch.searched
-    });
+    };
+
+    // The search is deterministic: report the searched nodes along with the time
+    bench.counter(ItemsCount::new(search())).bench_local(search);
 }
 
 #[divan::bench_group]
@@ -56,9 +74,9 @@ mod perft {
     #[divan::bench(items_count = PERFT_DEPTH.moves)]
     fn chusst() {
         use chusst_gen::eval::Game;
-        use chusst_gen::game::BitboardGame;
+        use chusst_gen::game::{BitboardGame, ModifiableGame};
 
-        fn possible_moves_recursive(game: BitboardGame, depth: u8) {
+        fn possible_moves_recursive(game: &mut BitboardGame, depth: u8) {
             if depth == 0 {
                 return;
             }
@@ -67,13 +85,13 @@ mod perft {
                 if depth == 1 {
                     continue;
                 }
-                let mut game = game.clone();
-                game.do_move(&mv);
+                let undo = game.do_move_no_checks(&mv).unwrap();
                 possible_moves_recursive(game, depth - 1);
+                game.undo_move(undo);
             }
         }
 
-        possible_moves_recursive(BitboardGame::new(), PERFT_DEPTH.depth);
+        possible_moves_recursive(&mut BitboardGame::new(), PERFT_DEPTH.depth);
     }
 
     #[divan::bench(items_count = PERFT_DEPTH.moves)]
@@ -128,7 +146,14 @@ fn game_benchmark() -> u64 {
 
     let get_best_move_helper = |game: &mut BitboardGame, history: &mut GameHistory| {
         let best_branch = game
-            .get_best_move_recursive(history, 3, &mut (), &mut SilentSearchFeedback::default())
+            .get_best_move_recursive(
+                &SearchLimits::from_depth(3),
+                &SearchOptions::default(),
+                history,
+                &TranspositionTable::default(),
+                &mut (),
+                &mut SilentSearchFeedback::default(),
+            )
             .unwrap();
 
         (best_branch.searched, best_branch.moves.first().unwrap().mv)
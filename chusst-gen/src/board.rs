@@ -208,6 +208,74 @@ impl fmt::Display for Position {
     }
 }
 
+/// Index of a square of the board, from 0 (a1) to 63 (h8), rank by rank.
+///
+/// Used internally by the bitboards, the hashes and the attack tables, where it is cheaper than
+/// a [`Position`] to turn into a bit or a table index.
+#[derive(Copy, Clone, Debug, Eq, Hash, PartialEq, PartialOrd, Ord)]
+pub struct Square(u8);
+
+impl Square {
+    pub const fn new(rank: usize, file: usize) -> Square {
+        debug_assert!(rank < 8 && file < 8);
+        Square((rank * 8 + file) as u8)
+    }
+
+    pub const fn from_index(index: usize) -> Square {
+        debug_assert!(index < 64);
+        Square(index as u8)
+    }
+
+    /// All the squares of the board, from a1 to h8.
+    pub fn all() -> impl Iterator<Item = Square> {
+        (0..64u8).map(Square)
+    }
+
+    pub const fn index(self) -> usize {
+        self.0 as usize
+    }
+
+    pub const fn rank(self) -> usize {
+        (self.0 / 8) as usize
+    }
+
+    pub const fn file(self) -> usize {
+        (self.0 % 8) as usize
+    }
+
+    /// Bit of the square in a mask of squares, such as a bitboard.
+    pub const fn mask(self) -> u64 {
+        1 << self.0
+    }
+}
+
+impl From<Position> for Square {
+    fn from(position: Position) -> Self {
+        Square::new(position.rank, position.file)
+    }
+}
+
+impl From<&Position> for Square {
+    fn from(position: &Position) -> Self {
+        Square::new(position.rank, position.file)
+    }
+}
+
+impl From<Square> for Position {
+    fn from(square: Square) -> Self {
+        Position {
+            rank: square.rank(),
+            file: square.file(),
+        }
+    }
+}
+
+impl fmt::Display for Square {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", Position::from(*self))
+    }
+}
+
 #[macro_export]
 macro_rules! pos {
     (a1) => {
@@ -427,15 +495,15 @@ pub trait IterableBoard
 where
     Self: Sized,
 {
-    fn try_move(&self, position: &Position, direction: &Direction) -> PositionIter<Self> {
+    fn try_move(&self, position: &Position, direction: &Direction) -> PositionIter<'_, Self> {
         PositionIter::new(self, try_move(position, direction))
     }
 
-    fn position_iter(&self, position: &Position) -> PositionIter<Self> {
+    fn position_iter(&self, position: &Position) -> PositionIter<'_, Self> {
         PositionIter::new(self, Some(*position))
     }
 
-    fn board_iter(&self) -> BoardIter<Self> {
+    fn board_iter(&self) -> BoardIter<'_, Self> {
         BoardIter::new(self)
     }
 
@@ -443,7 +511,7 @@ where
         &self,
         position: &Position,
         direction: &Direction,
-    ) -> DirectionIter<Self> {
+    ) -> DirectionIter<'_, Self> {
         DirectionIter::new(self, Some(*position), *direction)
     }
 }
@@ -461,7 +529,7 @@ pub trait Board:
     const NEW_BOARD: Self;
 
     fn iter(&self) -> impl Iterator<Item = Position> {
-        (0..8usize).flat_map(|rank| (0..8usize).map(move |file| Position { rank, file }))
+        Square::all().map(Position::from)
     }
 
     fn home_rank(player: &Player) -> usize {
@@ -622,7 +690,7 @@ fn get_unicode_piece(piece: PieceType, player: Player) -> char {
 fn format_board(board: &impl Board, f: &mut fmt::Formatter) -> fmt::Result {
     let mut rows: Vec<String> = Default::default();
 
-    let square_dark = |rank: usize, file: usize| -> bool { (rank + file) % 2 == 0 };
+    let square_dark = |rank: usize, file: usize| -> bool { (rank + file).is_multiple_of(2) };
 
     let is_atty = atty::is(atty::Stream::Stdout) && atty::is(atty::Stream::Stderr);
     let (left_square, right_square) = if is_atty { (" ", " ") } else { ("[", "]") };
@@ -697,3 +765,22 @@ where
     map.serialize_entry("ranks", &SerializableBoardRanks { board })?;
     map.end()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn square() {
+        assert_eq!(Square::from(pos!(a1)).index(), 0);
+        assert_eq!(Square::from(pos!(e4)).index(), 28);
+        assert_eq!(Square::from(pos!(h8)).index(), 63);
+
+        for (index, square) in Square::all().enumerate() {
+            let position = Position::from(square);
+            assert_eq!(square.index(), index);
+            assert_eq!(Square::from(position), square);
+            assert_eq!(format!("{}", square), format!("{}", position));
+        }
+    }
+}
@@ -1,34 +1,23 @@
 mod attack;
 mod in_between;
+mod magic;
 
 use serde::Serialize;
 
 use super::{
     format_board, serialize_board, Board, IterableBoard, ModifiableBoard, Piece, PieceType, Player,
-    Position,
+    Position, Square,
 };
 use std::fmt;
 
 type Bitboard = u64;
 
-fn rank_and_file_to_bitboard_index(rank: usize, file: usize) -> usize {
-    rank * 8 + file
+fn bitboard_from_square(square: Square) -> Bitboard {
+    square.mask()
 }
 
-fn position_to_bitboard_index(position: &Position) -> usize {
-    rank_and_file_to_bitboard_index(position.rank, position.file)
-}
-
-fn bitboard_from_rank_and_file(rank: usize, file: usize) -> Bitboard {
-    1 << rank_and_file_to_bitboard_index(rank, file)
-}
-
-fn bitboard_from_position(position: &Position) -> Bitboard {
-    bitboard_from_rank_and_file(position.rank, position.file)
-}
-
-fn check_bitboard(bitboard: Bitboard, position: &Position) -> bool {
-    bitboard & bitboard_from_position(position) != 0
+fn check_bitboard(bitboard: Bitboard, square: Square) -> bool {
+    bitboard & bitboard_from_square(square) != 0
 }
 
 #[derive(Clone, Debug, PartialEq)]
@@ -55,13 +44,10 @@ impl Iterator for BitboardIter {
             return None;
         }
 
-        let index = self.bitboard.trailing_zeros() as usize;
-        self.bitboard &= !(1 << index);
+        let square = Square::from_index(self.bitboard.trailing_zeros() as usize);
+        self.bitboard &= !bitboard_from_square(square);
 
-        let rank = index / 8;
-        let file = index % 8;
-
-        Some(Position { rank, file })
+        Some(square.into())
     }
 }
 
@@ -123,7 +109,11 @@ impl PlayerBitboards {
     }
 
     pub fn has_position(&self, position: &Position) -> bool {
-        check_bitboard(self.combined(), position)
+        check_bitboard(self.combined(), position.into())
+    }
+
+    pub fn position_mask(position: &Position) -> Bitboard {
+        bitboard_from_square(position.into())
     }
 
     pub fn into_iter(bitboard: Bitboard) -> BitboardIter {
@@ -154,33 +144,85 @@ impl PlayerBitboards {
     // Tables
 
     pub fn in_between(source: &Position, target: &Position) -> Bitboard {
-        let source_index = position_to_bitboard_index(source);
-        let target_index = position_to_bitboard_index(target);
-        in_between::IN_BETWEEN_TABLE[source_index][target_index]
+        in_between::IN_BETWEEN_TABLE[Square::from(source).index()][Square::from(target).index()]
     }
 
-    pub fn pawn_can_attack(&self, target_position: &Position) -> bool {
-        let target_index = position_to_bitboard_index(target_position);
+    pub fn pawn_attacks(player: &Player, position: &Position) -> Bitboard {
+        let index = Square::from(position).index();
+        match player {
+            Player::White => attack::WHITE_PAWN_ATTACK_TABLE[index],
+            Player::Black => attack::BLACK_PAWN_ATTACK_TABLE[index],
+        }
+    }
+
+    pub fn knight_attacks(position: &Position) -> Bitboard {
+        attack::KNIGHT_ATTACK_TABLE[Square::from(position).index()]
+    }
+
+    pub fn king_attacks(position: &Position) -> Bitboard {
+        attack::KING_ATTACK_TABLE[Square::from(position).index()]
+    }
+
+    // Sliding pieces attack until the first piece of the occupancy, included
+
+    pub fn bishop_attacks(position: &Position, occupancy: Bitboard) -> Bitboard {
+        magic::BISHOP_ATTACKS.get(position.into(), occupancy)
+    }
+
+    pub fn rook_attacks(position: &Position, occupancy: Bitboard) -> Bitboard {
+        magic::ROOK_ATTACKS.get(position.into(), occupancy)
+    }
+
+    pub fn queen_attacks(position: &Position, occupancy: Bitboard) -> Bitboard {
+        Self::bishop_attacks(position, occupancy) | Self::rook_attacks(position, occupancy)
+    }
+
+    pub fn pawn_attackers(&self, target_position: &Position) -> Bitboard {
+        let target_index = Square::from(target_position).index();
         match self.player {
-            Player::White => self.pawns & attack::BLACK_ATTACKED_BY_PAWN_TABLE[target_index] != 0,
-            Player::Black => self.pawns & attack::WHITE_ATTACKED_BY_PAWN_TABLE[target_index] != 0,
+            Player::White => self.pawns & attack::BLACK_ATTACKED_BY_PAWN_TABLE[target_index],
+            Player::Black => self.pawns & attack::WHITE_ATTACKED_BY_PAWN_TABLE[target_index],
         }
     }
 
+    pub fn knight_attackers(&self, target_position: &Position) -> Bitboard {
+        let target_index = Square::from(target_position).index();
+        self.knights & attack::ATTACKED_BY_KNIGHT_TABLE[target_index]
+    }
+
+    pub fn king_attackers(&self, target_position: &Position) -> Bitboard {
+        let target_index = Square::from(target_position).index();
+        self.kings & attack::ATTACKED_BY_KING_TABLE[target_index]
+    }
+
+    pub fn bishop_attackers(&self, target_position: &Position, occupancy: Bitboard) -> Bitboard {
+        self.bishops & Self::bishop_attacks(target_position, occupancy)
+    }
+
+    pub fn rook_attackers(&self, target_position: &Position, occupancy: Bitboard) -> Bitboard {
+        self.rooks & Self::rook_attacks(target_position, occupancy)
+    }
+
+    pub fn queen_attackers(&self, target_position: &Position, occupancy: Bitboard) -> Bitboard {
+        self.queens & Self::queen_attacks(target_position, occupancy)
+    }
+
+    pub fn pawn_can_attack(&self, target_position: &Position) -> bool {
+        self.pawn_attackers(target_position) != 0
+    }
+
     pub fn knight_can_attack(&self, target_position: &Position) -> bool {
-        let target_index = position_to_bitboard_index(target_position);
-        self.knights & attack::ATTACKED_BY_KNIGHT_TABLE[target_index] != 0
+        self.knight_attackers(target_position) != 0
     }
 
     pub fn king_can_attack(&self, target_position: &Position) -> bool {
-        let target_index = position_to_bitboard_index(target_position);
-        self.kings & attack::ATTACKED_BY_KING_TABLE[target_index] != 0
+        self.king_attackers(target_position) != 0
     }
 }
 
 impl ModifiableBoard<Position, Option<PieceType>> for PlayerBitboards {
     fn at(&self, index: &Position) -> Option<PieceType> {
-        let position_mask = bitboard_from_position(index);
+        let position_mask = bitboard_from_square(index.into());
         if self.pawns & position_mask != 0 {
             Some(PieceType::Pawn)
         } else if self.knights & position_mask != 0 {
@@ -199,9 +241,13 @@ impl ModifiableBoard<Position, Option<PieceType>> for PlayerBitboards {
     }
 
     fn update(&mut self, pos: &Position, value: Option<PieceType>) {
-        let mask = bitboard_from_position(pos);
+        let mask = bitboard_from_square(pos.into());
         match value {
             Some(piece) => {
+                // Replace the previous piece, like in a promotion
+                let negate_mask = !mask;
+                self.apply(|bitboard: Bitboard| bitboard & negate_mask);
+
                 match piece {
                     PieceType::Pawn => self.pawns |= mask,
                     PieceType::Knight => self.knights |= mask,
@@ -221,8 +267,8 @@ impl ModifiableBoard<Position, Option<PieceType>> for PlayerBitboards {
     }
 
     fn move_piece(&mut self, source: &Position, target: &Position) {
-        let source_mask = bitboard_from_position(source);
-        let set_mask = bitboard_from_position(target);
+        let source_mask = bitboard_from_square(source.into());
+        let set_mask = bitboard_from_square(target.into());
         let clear_mask = !set_mask;
         let move_bit = |bitboard: Bitboard| -> Bitboard {
             if bitboard & source_mask != 0 {
@@ -239,17 +285,15 @@ impl fmt::Display for PlayerBitboards {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         let mut board = Bitboards::default();
 
-        for rank in 0..8 {
-            for file in 0..8 {
-                let pos = Position { rank, file };
-                board.update(
-                    &pos,
-                    self.at(&pos).map(|piece| Piece {
-                        piece,
-                        player: self.player,
-                    }),
-                );
-            }
+        for square in Square::all() {
+            let pos = Position::from(square);
+            board.update(
+                &pos,
+                self.at(&pos).map(|piece| Piece {
+                    piece,
+                    player: self.player,
+                }),
+            );
         }
 
         write!(f, "{}", board)
@@ -297,29 +341,27 @@ impl Bitboards {
     pub fn from(other: &impl Board) -> Self {
         let mut bitboards = Bitboards::default();
 
-        for rank in 0..8 {
-            for file in 0..8 {
-                let Some(square) = other.at(&Position { rank, file }) else {
-                    continue;
-                };
-                let player_bitboards = match square.player {
-                    Player::White => &mut bitboards.white,
-                    Player::Black => &mut bitboards.black,
-                };
-
-                let piece_mask = bitboard_from_rank_and_file(rank, file);
-
-                match square.piece {
-                    PieceType::Pawn => player_bitboards.pawns |= piece_mask,
-                    PieceType::Knight => player_bitboards.knights |= piece_mask,
-                    PieceType::Bishop => player_bitboards.bishops |= piece_mask,
-                    PieceType::Rook => player_bitboards.rooks |= piece_mask,
-                    PieceType::Queen => player_bitboards.queens |= piece_mask,
-                    PieceType::King => player_bitboards.kings |= piece_mask,
-                }
-
-                player_bitboards.combined |= piece_mask;
+        for square in Square::all() {
+            let Some(piece) = other.at(&square.into()) else {
+                continue;
+            };
+            let player_bitboards = match piece.player {
+                Player::White => &mut bitboards.white,
+                Player::Black => &mut bitboards.black,
+            };
+
+            let piece_mask = bitboard_from_square(square);
+
+            match piece.piece {
+                PieceType::Pawn => player_bitboards.pawns |= piece_mask,
+                PieceType::Knight => player_bitboards.knights |= piece_mask,
+                PieceType::Bishop => player_bitboards.bishops |= piece_mask,
+                PieceType::Rook => player_bitboards.rooks |= piece_mask,
+                PieceType::Queen => player_bitboards.queens |= piece_mask,
+                PieceType::King => player_bitboards.kings |= piece_mask,
             }
+
+            player_bitboards.combined |= piece_mask;
         }
 
         bitboards
@@ -6,7 +6,6 @@ use super::Bitboard;
  * ----------------------------------------------------------------------------
  */
 
-#[allow(dead_code)]
 pub const WHITE_PAWN_ATTACK_TABLE: [Bitboard; 64] = [
     0,
     0,
@@ -74,7 +73,6 @@ pub const WHITE_PAWN_ATTACK_TABLE: [Bitboard; 64] = [
     0,
 ];
 
-#[allow(dead_code)]
 pub const BLACK_PAWN_ATTACK_TABLE: [u64; 64] = [
     0,
     0,
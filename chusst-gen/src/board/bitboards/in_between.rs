@@ -1,6 +1,6 @@
 use super::Bitboard;
 
-pub const IN_BETWEEN_TABLE: [[Bitboard; 64]; 64] = [
+pub static IN_BETWEEN_TABLE: [[Bitboard; 64]; 64] = [
     [
         0,
         0,
@@ -4229,15 +4229,15 @@ pub const IN_BETWEEN_TABLE: [[Bitboard; 64]; 64] = [
 
 #[cfg(test)]
 mod tests {
-    use super::super::rank_and_file_to_bitboard_index;
     use super::*;
-    use crate::board::Position;
+    use crate::board::{Position, Square};
+
+    fn rank_and_file_to_bitboard_index(rank: usize, file: usize) -> usize {
+        Square::new(rank, file).index()
+    }
 
     fn bitboard_index_to_position(index: usize) -> Position {
-        Position {
-            rank: index / 8,
-            file: index % 8,
-        }
+        Square::from_index(index).into()
     }
 
     fn format_bitboard(bitboard: Bitboard) -> String {
@@ -4282,8 +4282,7 @@ mod tests {
 
                     for _ in 1..file_distance {
                         file = (file as i8 + file_increment) as usize;
-                        in_between_rank |=
-                            1 << rank_and_file_to_bitboard_index(source.rank, file as usize);
+                        in_between_rank |= 1 << rank_and_file_to_bitboard_index(source.rank, file);
                     }
                     assert_eq!(
                         *in_between,
@@ -4301,8 +4300,7 @@ mod tests {
 
                     for _ in 1..rank_distance {
                         rank = (rank as i8 + rank_increment) as usize;
-                        in_between_file |=
-                            1 << rank_and_file_to_bitboard_index(rank as usize, source.file);
+                        in_between_file |= 1 << rank_and_file_to_bitboard_index(rank, source.file);
                     }
                     assert_eq!(*in_between, in_between_file);
                 } else if same_diagonal {
@@ -1,4 +1,4 @@
-use super::{ModifiableBoard, Piece, PieceType, Player, Position};
+use super::{ModifiableBoard, Piece, PieceType, Player, Position, Square};
 use crate::pos;
 use std::marker::PhantomData;
 
@@ -165,7 +165,7 @@ impl<'a, R> DirectionIter<'a, R> {
 
 pub struct BoardIter<'a, R> {
     representation: &'a R,
-    position: Position,
+    index: usize,
 }
 
 impl<'a, R> PositionIterator<'a, R> for BoardIter<'a, R> {
@@ -178,7 +178,7 @@ impl<'a, R> Clone for BoardIter<'a, R> {
     fn clone(&self) -> Self {
         Self {
             representation: self.representation,
-            position: self.position,
+            index: self.index,
         }
     }
 }
@@ -187,20 +187,14 @@ impl<'a, R> Iterator for BoardIter<'a, R> {
     type Item = Position;
 
     fn next(&mut self) -> Option<Self::Item> {
-        if self.position.rank > 7 {
+        if self.index >= 64 {
             return None;
         }
 
-        let current_position = self.position;
-
-        if self.position.file == 7 {
-            self.position.rank += 1;
-            self.position.file = 0;
-        } else {
-            self.position.file += 1;
-        }
+        let current_square = Square::from_index(self.index);
+        self.index += 1;
 
-        Some(current_position)
+        Some(current_square.into())
     }
 }
 
@@ -208,7 +202,7 @@ impl<'a, R> BoardIter<'a, R> {
     pub fn new(representation: &'a R) -> Self {
         BoardIter {
             representation,
-            position: Position { rank: 0, file: 0 },
+            index: 0,
         }
     }
 }
@@ -1,32 +1,53 @@
+mod book;
 mod check;
 mod conditions;
+mod evaluation;
 mod feedback;
 mod history;
 mod iter;
+mod limits;
+mod options;
+mod ordering;
 mod play;
+mod tablebase;
+mod transposition;
 
 #[cfg(test)]
 mod tests;
 
-use self::check::{only_empty_and_safe, SafetyChecks};
+pub use self::book::{BookPolicy, OpeningBook};
+use self::check::{CheckMasks, SafetyChecks};
+pub use self::evaluation::evaluate;
 pub use self::feedback::{
     EngineFeedback, EngineFeedbackMessage, EngineMessage, SilentSearchFeedback, StdoutFeedback,
 };
-use self::feedback::{PeriodicalSearchFeedback, SearchFeedback};
+use self::feedback::{PeriodicalSearchFeedback, SearchFeedback, SearchIteration};
 pub use self::history::GameHistory;
 use self::history::HashedHistory;
 pub use self::iter::dir;
-use self::iter::piece_into_iter;
+use self::iter::PieceTargets;
+pub use self::limits::SearchLimits;
+use self::limits::{NodesStopSignal, SharedStopSignal};
+pub use self::options::SearchOptions;
+use self::ordering::{is_quiet, mvv_lva, MoveOrdering};
 use self::play::PlayableGame;
-use crate::board::{Board, Direction, Piece, PieceType, Player, Position, PositionIterator, Ranks};
-use crate::game::{GameState, ModifiableGame, Move, MoveAction, MoveActionType, PromotionPieces};
-use crate::{mv, mva, pos};
+pub use self::tablebase::Tablebase;
+use self::tablebase::Wdl;
+pub use self::transposition::TranspositionTable;
+use self::transposition::{score_from_table, score_to_table, Bound, TranspositionEntry};
+use crate::board::{Board, Piece, PieceType, Player, Position, PositionIterator, Ranks};
+use crate::game::{
+    GameState, ModifiableGame, MoveAction, MoveActionType, MoveExtraInfo, MoveUndo, PackedMove,
+    PromotionPieces,
+};
+use crate::pos;
 
 use anyhow::{bail, Result};
 use core::{fmt, panic};
 use serde::Serialize;
 
 use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::time::Instant;
 
 pub trait HasStopSignal {
@@ -75,9 +96,49 @@ impl Score {
         }
     }
 
-    // Score lower than losing any piece, but higher than stalemate
+    // Score of a checkmate in 0 plies, mates further away score lower
+    const MATE: i32 = 1_000_000_000;
+    // Longest mate that can be told apart from any other score
+    const MAX_MATE_PLIES: i32 = 10_000;
+
+    // Score of a position won according to the endgame tablebases, higher than any material
+    // advantage but lower than any mate
+    const TABLEBASE_WIN: i32 = 100_000;
+
+    // Score lower than losing any piece, but higher than being checkmated
     pub fn stalemate() -> Score {
-        Score::from(Self::MIN.0 / 2)
+        Score::from(-Self::MATE / 2)
+    }
+
+    // Score of the player that gives checkmate in this number of plies
+    pub fn mate_in(plies: u32) -> Score {
+        Score::from(Self::MATE - plies as i32)
+    }
+
+    // Score of the player that wins according to the endgame tablebases, where the win is secured
+    // in this number of plies
+    pub fn tablebase_win(plies: u32) -> Score {
+        Score::from(Self::TABLEBASE_WIN - plies as i32)
+    }
+
+    pub fn is_mate(&self) -> bool {
+        self.0.abs() > Self::MATE - Self::MAX_MATE_PLIES && self.0.abs() <= Self::MATE
+    }
+
+    // Plies until checkmate, if this is a mate score
+    pub fn mate_plies(&self) -> Option<u32> {
+        self.is_mate()
+            .then(|| (Self::MATE - self.0.abs()).unsigned_abs())
+    }
+
+    // Moves until checkmate, negative if the player is checkmated, if this is a mate score
+    pub fn mate_moves(&self) -> Option<i32> {
+        let plies = self.mate_plies()? as i32;
+        if self.0 > 0 {
+            Some((plies + 1) / 2)
+        } else {
+            Some(-plies / 2)
+        }
     }
 }
 
@@ -99,6 +160,8 @@ impl std::fmt::Display for Score {
             write!(f, "-∞")
         } else if *self >= Self::MAX {
             write!(f, "+∞")
+        } else if let Some(moves) = self.mate_moves() {
+            write!(f, "#{}", moves)
         } else {
             self.0.fmt(f)
         }
@@ -183,23 +246,126 @@ struct SearchResult {
     stopped: bool,
 }
 
+struct QuiescenceResult {
+    score: Score,
+    searched: u32,
+    stopped: bool,
+}
+
+#[derive(Copy, Clone)]
 struct SearchScores {
-    parent: Score,
     alpha: Score,
     beta: Score,
 }
 
+// Plies skipped by the search after a null move, on top of the null move itself
+const NULL_MOVE_REDUCTION: u32 = 2;
+// Moves searched at full depth in each position before the late move reductions start
+const LMR_FULL_DEPTH_MOVES: usize = 3;
+// Minimum remaining depth to reduce a late move by one ply
+const LMR_MIN_DEPTH: u32 = 2;
+// Maximum plies added by the check and single reply extensions to a branch
+const MAX_EXTENSION_PLIES: u32 = 4;
+// Plies of history reserved before the search, which is unbounded when limited by time
+const MAX_RESERVED_PLIES: u32 = 128;
+// Depth after which the quiescence search stands pat in check, to end sequences of checks
+const MAX_QUIESCENCE_PLIES: u32 = 32;
+// Initial distance of the root window bounds to the expected score, in centipawns
+const ASPIRATION_WINDOW: i32 = 50;
+// A bound of the root window further than this from the expected score is removed
+const MAX_ASPIRATION_WINDOW: i32 = 1000;
+
+struct SearchContext<'a> {
+    history: HashedHistory,
+    transposition_table: &'a TranspositionTable,
+    move_ordering: MoveOrdering,
+    // Maximum depth reached in the current iteration, including the quiescence search
+    selective_depth: u32,
+    // Moves searched since the start of the search, including the quiescence search
+    nodes: u32,
+    // Moves of the root position that are searched, all of them if empty
+    search_moves: &'a [MoveAction],
+    // Moves of the root position that are not searched, already found by previous lines
+    excluded_root_moves: Vec<PackedMove>,
+    // Plies added by the search extensions to the branch being searched
+    extensions: u32,
+    // Search with principal variation search instead of plain alpha-beta
+    principal_variation_search: bool,
+    tablebase: Option<&'a Tablebase>,
+    // Pieces on the board of the position being searched, to know if it can be in the tablebases.
+    // Only counted when searching with tablebases.
+    pieces: usize,
+    // Scores of the root moves according to the endgame tablebases, if the root is in them
+    root_tablebase_scores: &'a [WeightedMove],
+}
+
+impl<'a> SearchContext<'a> {
+    // Context of a search of all the moves, with plain alpha-beta and without tablebases
+    fn new(history: HashedHistory, transposition_table: &'a TranspositionTable) -> Self {
+        SearchContext {
+            history,
+            transposition_table,
+            move_ordering: MoveOrdering::default(),
+            selective_depth: 0,
+            nodes: 0,
+            search_moves: &[],
+            excluded_root_moves: Vec::new(),
+            extensions: 0,
+            principal_variation_search: false,
+            tablebase: None,
+            pieces: 0,
+            root_tablebase_scores: &[],
+        }
+    }
+}
+
 impl Default for SearchScores {
     fn default() -> Self {
         Self {
-            parent: Score::from(0),
             alpha: Score::MIN,
             beta: Score::MAX,
         }
     }
 }
 
-impl<B: Board + SafetyChecks> PlayableGame<B> for GameState<B> {
+// Score of the move into a position of the endgame tablebases at this depth, so that faster
+// wins score higher. Draws score an even material, above any loss and below any win.
+fn tablebase_score(wdl: Wdl, current_depth: u32) -> Score {
+    match wdl {
+        Wdl::Win => -Score::tablebase_win(current_depth),
+        Wdl::Loss => Score::tablebase_win(current_depth),
+        // Draws by the fifty-move rule, slightly better for the player that would win otherwise
+        Wdl::CursedWin => Score::from(-1),
+        Wdl::BlessedLoss => Score::from(1),
+        Wdl::Draw => Score::from(0),
+    }
+}
+
+// Score of a root move with this distance to zeroing, positive if it wins. Wins and losses that
+// take longer than the fifty-move rule are draws.
+fn tablebase_root_score(dtz: i32) -> Score {
+    match dtz {
+        0 => Score::from(0),
+        1..=100 => Score::tablebase_win(dtz.unsigned_abs()),
+        -100..=-1 => -Score::tablebase_win(dtz.unsigned_abs()),
+        _ => Score::from(dtz.signum()),
+    }
+}
+
+// Material won by a move
+fn move_score(board: &impl Board, move_action: &MoveAction) -> Score {
+    match board.at(&move_action.mv.target) {
+        Some(piece) => Score::piece_value(piece.piece),
+        None => match move_action.move_type {
+            MoveActionType::Promotion(promotion_piece) => {
+                Score::piece_value(promotion_piece.into())
+            }
+            _ => Score::from(0),
+        },
+    }
+}
+
+impl<B: Board + SafetyChecks + PieceTargets> PlayableGame<B> for GameState<B> {
     fn as_ref(&self) -> &GameState<B> {
         self
     }
@@ -208,72 +374,12 @@ impl<B: Board + SafetyChecks> PlayableGame<B> for GameState<B> {
         self
     }
 
-    fn do_move_no_checks(&mut self, move_action: &MoveAction) -> anyhow::Result<()> {
+    fn do_move_no_checks(&mut self, move_action: &MoveAction) -> anyhow::Result<MoveUndo> {
         ModifiableGame::do_move_no_checks(self, move_action)
     }
 }
 
-trait GamePrivate<B: Board + SafetyChecks>: PlayableGame<B> + ModifiableGame<B> {
-    // Only for moves from the move generator, not from unknown sources
-    fn clone_and_move_with_checks(
-        &self,
-        move_action: &MoveAction,
-        king_position: &Position,
-        reset_hash: bool,
-    ) -> Option<GameState<B>> {
-        let mv = &move_action.mv;
-        let is_king = mv.source == *king_position;
-        let player = self.board().at(king_position).unwrap().player;
-
-        // Before moving, check if it is a castling and it is valid
-        if is_king && mv.source.file.abs_diff(mv.target.file) == 2 {
-            let is_valid_castling_square = |direction: &Direction| {
-                only_empty_and_safe(
-                    self.board(),
-                    self.try_move(&mv.source, direction).next(),
-                    &player,
-                )
-                .is_some()
-            };
-            let can_castle = match mv.target.file {
-                // Queenside
-                2 => {
-                    is_valid_castling_square(&dir!(0, -1)) && is_valid_castling_square(&dir!(0, -2))
-                }
-                // Kingside
-                6 => is_valid_castling_square(&dir!(0, 1)) && is_valid_castling_square(&dir!(0, 2)),
-                _ => panic!(
-                    "invalid castling {} in:\n{}\n,game info is {}",
-                    mv,
-                    self.board(),
-                    self.info()
-                ),
-            };
-            let castling_is_safe = can_castle && !self.board().is_piece_unsafe(king_position);
-
-            if !castling_is_safe {
-                return None;
-            }
-        }
-
-        // Move
-        let new_game = self.clone_and_move(move_action, reset_hash).ok()?;
-
-        // After moving, check if the king is in check
-
-        let current_king_position = if is_king { &mv.target } else { king_position };
-
-        if new_game
-            .as_ref()
-            .board()
-            .is_piece_unsafe(current_king_position)
-        {
-            return None;
-        }
-
-        Some(new_game)
-    }
-
+trait GamePrivate<B: Board + SafetyChecks + PieceTargets>: PlayableGame<B> + ModifiableGame<B> {
     fn get_possible_moves_iter<'a>(
         &'a self,
         position: Position,
@@ -281,11 +387,11 @@ trait GamePrivate<B: Board + SafetyChecks>: PlayableGame<B> + ModifiableGame<B>
     where
         B: 'a,
     {
-        piece_into_iter(self.as_ref(), position)
+        B::piece_targets(self.as_ref(), position)
     }
 
-    fn get_possible_moves_no_checks(&self, position: Position) -> Vec<MoveAction> {
-        let mut possible_moves: Vec<MoveAction> = Vec::new();
+    fn get_possible_moves_no_checks(&self, position: Position) -> Vec<PackedMove> {
+        let mut possible_moves: Vec<PackedMove> = Vec::new();
 
         let is_pawn = match self.board().at(&position) {
             Some(piece) => piece.piece == PieceType::Pawn,
@@ -296,56 +402,60 @@ trait GamePrivate<B: Board + SafetyChecks>: PlayableGame<B> + ModifiableGame<B>
         for target in self.get_possible_moves_iter(position) {
             if can_promote {
                 // I don't think there is an easy way to iterate through all the values of an enum :(
-                possible_moves.push(mva!(position, target));
-                possible_moves.push(MoveAction {
-                    mv: mv!(position, target),
-                    move_type: MoveActionType::Promotion(PromotionPieces::Knight),
-                });
-                possible_moves.push(MoveAction {
-                    mv: mv!(position, target),
-                    move_type: MoveActionType::Promotion(PromotionPieces::Bishop),
-                });
-                possible_moves.push(MoveAction {
-                    mv: mv!(position, target),
-                    move_type: MoveActionType::Promotion(PromotionPieces::Rook),
-                });
-                possible_moves.push(MoveAction {
-                    mv: mv!(position, target),
-                    move_type: MoveActionType::Promotion(PromotionPieces::Queen),
-                });
+                for piece in [
+                    PromotionPieces::Knight,
+                    PromotionPieces::Bishop,
+                    PromotionPieces::Rook,
+                    PromotionPieces::Queen,
+                ] {
+                    possible_moves.push(PackedMove::new(
+                        &position,
+                        &target,
+                        MoveExtraInfo::Promotion(piece),
+                    ));
+                }
             } else {
-                possible_moves.push(MoveAction {
-                    mv: mv!(position, target),
-                    move_type: MoveActionType::Normal,
-                });
+                possible_moves.push(PackedMove::new(&position, &target, MoveExtraInfo::Other));
             }
         }
 
         possible_moves
     }
 
+    // Legal moves of the pieces at the positions, which must be of the player to move
+    fn get_legal_moves(
+        &self,
+        check_masks: &CheckMasks,
+        positions: impl IntoIterator<Item = Position>,
+    ) -> Vec<PackedMove> {
+        positions
+            .into_iter()
+            .flat_map(|position| self.get_possible_moves_no_checks(position))
+            .filter(|mv| check_masks.is_legal(self.board(), mv))
+            .collect()
+    }
+
     fn get_possible_moves_from_game(&self, position: Position) -> Vec<MoveAction> {
-        let Some(Piece { piece, player: _ }) = self.board().at(&position) else {
+        if self.board().at(&position).is_none() {
             return vec![];
-        };
-        let is_king = piece == PieceType::King;
-
-        let king_position = if is_king {
-            position
-        } else {
-            self.board().find_king(&self.player())
-        };
+        }
 
-        self.get_possible_moves_no_checks(position)
-            .iter()
-            .filter(|mv| {
-                self.clone_and_move_with_checks(mv, &king_position, true)
-                    .is_some()
-            })
-            .copied()
+        let check_masks = CheckMasks::new(self.board(), &self.player());
+        self.get_legal_moves(&check_masks, [position])
+            .into_iter()
+            .map(MoveAction::from)
             .collect()
     }
 
+    // Positions with only pawns are prone to zugzwang, where any move is worse than passing
+    fn has_non_pawn_material(&self, player: Player) -> bool {
+        self.board_iter().only_player(player).any(|position| {
+            self.board()
+                .at(&position)
+                .is_some_and(|piece| !matches!(piece.piece, PieceType::Pawn | PieceType::King))
+        })
+    }
+
     fn move_branch_names(&self, moves: &Vec<&MoveAction>) -> Vec<String> {
         let mut new_game = self.as_ref().clone();
 
@@ -362,17 +472,102 @@ trait GamePrivate<B: Board + SafetyChecks>: PlayableGame<B> + ModifiableGame<B>
 
     // Negamax with alpha-beta pruning
     fn get_best_move_recursive_alpha_beta(
-        &self,
+        &mut self,
         current_depth: u32,
         max_depth: u32,
         scores: SearchScores,
-        history: &mut HashedHistory,
+        context: &mut SearchContext,
         stop_signal: &mut impl HasStopSignal,
         feedback: &mut impl SearchFeedback,
     ) -> SearchResult {
-        let board = self.board();
         let player = self.player();
 
+        let remaining_depth = max_depth - current_depth;
+        let hash = self.as_ref().cached_hash();
+        let tt_entry = hash.and_then(|hash| context.transposition_table.get(&hash));
+
+        // Transposition table cutoff, except at the root where a move must be returned
+        if let Some(entry) =
+            tt_entry.filter(|entry| current_depth > 0 && entry.depth >= remaining_depth)
+        {
+            let score = score_from_table(entry.score, current_depth);
+            let is_usable = match entry.bound {
+                Bound::Exact => true,
+                Bound::Lower => score >= scores.beta,
+                Bound::Upper => score <= scores.alpha,
+            };
+
+            if is_usable {
+                return SearchResult {
+                    branch: Some(Branch {
+                        moves: entry
+                            .best_move
+                            .map(MoveAction::from)
+                            .map(|mv| WeightedMove {
+                                mv,
+                                score: move_score(self.board(), &mv),
+                            })
+                            .into_iter()
+                            .collect(),
+                        score,
+                        searched: 0,
+                        result: entry.result,
+                    }),
+                    stopped: false,
+                };
+            }
+        }
+
+        let check_masks = CheckMasks::new(self.board(), &player);
+        let is_check = check_masks.is_check();
+
+        // Null-move pruning: if passing the turn still fails high, a real move will most likely
+        // fail high too. Not done after another null move, which leaves no last move.
+        let try_null_move = current_depth > 0
+            && remaining_depth > NULL_MOVE_REDUCTION
+            && !is_check
+            && self.last_move().is_some()
+            && scores.beta < Score::MAX
+            && !scores.beta.is_mate()
+            && self.has_non_pawn_material(player)
+            && evaluate(self.as_ref()) >= scores.beta;
+
+        if try_null_move {
+            let data = *self.as_ref().data();
+            self.as_mut().do_null_move();
+
+            let null_move_result = self.get_best_move_recursive_alpha_beta(
+                current_depth + 1,
+                max_depth - NULL_MOVE_REDUCTION,
+                // Null window around beta
+                SearchScores {
+                    alpha: -scores.beta,
+                    beta: -scores.beta + Score::from(1),
+                },
+                context,
+                stop_signal,
+                feedback,
+            );
+
+            self.as_mut().set_data(&data);
+
+            let null_move_score = null_move_result.branch.as_ref().map(|branch| -branch.score);
+
+            if null_move_result.stopped || null_move_score.is_some_and(|score| score >= scores.beta)
+            {
+                // Fail hard, mates found after passing are not real
+                return SearchResult {
+                    branch: Some(Branch {
+                        moves: Vec::new(),
+                        score: scores.beta,
+                        searched: null_move_result.branch.map_or(0, |branch| branch.searched),
+                        result: None,
+                    }),
+                    stopped: null_move_result.stopped,
+                };
+            }
+        }
+
         let pieces_iter = self
             .board_iter()
             .only_player(player)
@@ -382,11 +577,8 @@ trait GamePrivate<B: Board + SafetyChecks>: PlayableGame<B> + ModifiableGame<B>
 
         let mut searched_moves: u32 = 0;
 
-        let king_position = self.board().find_king(&player);
-
         let mut local_alpha = scores.alpha;
 
-        let is_leaf_node = current_depth == max_depth;
         let mut stopped = false;
 
         #[cfg(feature = "verbose-search")]
@@ -396,211 +588,367 @@ trait GamePrivate<B: Board + SafetyChecks>: PlayableGame<B> + ModifiableGame<B>
                 .collect::<String>()
         };
 
-        'main_loop: for player_piece_position in pieces_iter {
-            for possible_move in self.get_possible_moves_no_checks(player_piece_position) {
-                if stop_signal.stop() {
-                    let _ = writeln!(feedback, "Search stopped");
-                    stopped = true;
-                    break 'main_loop;
-                }
+        let mut moves = self.get_legal_moves(&check_masks, pieces_iter);
+
+        // Only the requested moves are searched, and with multi-PV the best moves of the
+        // previous lines are skipped
+        let is_partial_root = current_depth == 0
+            && !(context.search_moves.is_empty() && context.excluded_root_moves.is_empty());
+        if is_partial_root {
+            moves.retain(|mv| {
+                (context.search_moves.is_empty()
+                    || context.search_moves.contains(&MoveAction::from(*mv)))
+                    && !context.excluded_root_moves.contains(mv)
+            });
+        }
 
-                let mut cutoff = false;
+        let is_single_reply = is_check && moves.len() == 1;
 
-                let mv = &possible_move.mv;
-                let possible_position = &mv.target;
-                searched_moves += 1;
+        let hash_move = tt_entry.and_then(|entry| entry.best_move);
+        context.move_ordering.sort(
+            self.board(),
+            &player,
+            &mut moves,
+            hash_move.as_ref(),
+            current_depth,
+        );
 
-                // Evaluate this move locally
-                let local_score = match &board.at(possible_position) {
-                    Some(piece) => Score::piece_value(piece.piece),
-                    None => {
-                        match possible_move.move_type {
-                            MoveActionType::Promotion(promotion_piece) => {
-                                // Promotion
-                                Score::piece_value(promotion_piece.into())
-                            }
-                            _ => Score::from(0),
-                        }
-                    }
-                };
+        for (move_index, possible_move) in moves.into_iter().enumerate() {
+            if stop_signal.stop() {
+                let _ = writeln!(feedback, "Search stopped");
+                stopped = true;
+                break;
+            }
 
-                let Some(mut recursive_game) =
-                    self.clone_and_move_with_checks(&possible_move, &king_position, false)
-                else {
-                    continue;
-                };
+            let mut cutoff = false;
 
-                // Threefold repetition
-                let hash = recursive_game.hash();
-                history.push(possible_move, hash);
-                let repetition_count = history.count(&hash);
-                let threefold_repetition = repetition_count >= 3;
-
-                let mut branch = Branch {
-                    moves: vec![WeightedMove {
-                        mv: possible_move,
-                        score: local_score,
-                    }],
-                    // Negamax: negate score from previous move
-                    score: local_score - scores.parent,
-                    searched: 0,
-                    result: None,
+            searched_moves += 1;
+            context.nodes += 1;
+
+            let move_action = MoveAction::from(possible_move);
+
+            if current_depth == 0 {
+                feedback.current_move(&move_action, move_index as u32 + 1);
+            }
+            context.selective_depth = context.selective_depth.max(current_depth + 1);
+
+            // Evaluate this move locally
+            let local_score = move_score(self.board(), &move_action);
+            let is_quiet_move = is_quiet(self.board(), &possible_move);
+
+            let Ok(undo) = PlayableGame::do_move_no_checks(self, &move_action) else {
+                continue;
+            };
+
+            // Checks and forced replies are searched one ply deeper, so that forcing sequences
+            // are not cut off at the last ply
+            let gives_check = self
+                .board()
+                .is_piece_unsafe(&self.board().find_king(&!player));
+            let extension = u32::from(
+                (gives_check || is_single_reply) && context.extensions < MAX_EXTENSION_PLIES,
+            );
+            let child_max_depth = max_depth + extension;
+            let is_leaf_node = current_depth == child_max_depth;
+
+            // The endgame tablebases know the result of the position with perfect play. They are
+            // only probed when a capture or a pawn move reaches them, like the fifty-move rule.
+            let pieces = context
+                .pieces
+                .saturating_sub(usize::from(undo.is_capture()));
+            let tablebase_wdl = context
+                .tablebase
+                .filter(|tablebase| undo.is_zeroing() && pieces <= tablebase.max_pieces())
+                .and_then(|tablebase| tablebase.probe_wdl(self.as_ref()));
+
+            // Threefold repetition
+            let hash = self.as_mut().hash();
+            context.history.push(possible_move, hash);
+            let repetition_count = context.history.count(&hash);
+            let threefold_repetition = repetition_count >= 3;
+
+            let mut branch = Branch {
+                moves: vec![WeightedMove {
+                    mv: move_action,
+                    score: local_score,
+                }],
+                // Preliminary score until the move is evaluated
+                score: local_score,
+                searched: 0,
+                result: None,
+            };
+
+            feedback.update(context.nodes);
+
+            #[cfg(feature = "verbose-search")]
+            {
+                let _ = writeln!(
+                    feedback,
+                    "{}{{\"{}\": \"{} {:+} α: {}, β: {}\"{}",
+                    indent(current_depth),
+                    player,
+                    move_action.mv,
+                    branch.score,
+                    local_alpha,
+                    scores.beta,
+                    if !is_leaf_node { ", \"s\": [" } else { "}," },
+                );
+            }
+
+            // Recursion
+            if threefold_repetition {
+                // Enforce draw
+                branch.score = Score::stalemate();
+                branch.result = Some(GameResult::Draw);
+            } else if let Some(wdl) = tablebase_wdl {
+                branch.score = tablebase_score(wdl, current_depth + 1);
+            } else if !is_leaf_node {
+                // Late move reductions: quiet moves sorted last are unlikely to be the best, so
+                // they are searched one ply shallower with a null window, and searched again at
+                // full depth only if they improve alpha
+                let is_late_move = move_index >= LMR_FULL_DEPTH_MOVES
+                    && remaining_depth >= LMR_MIN_DEPTH
+                    && is_quiet_move
+                    && !is_check
+                    && local_alpha > Score::MIN
+                    && !local_alpha.is_mate()
+                    && !gives_check;
+
+                // Principal variation search: once there is a best move, the rest of the moves
+                // are expected to be worse and are only searched with a null window to prove it.
+                // They are searched again with the full window if they turn out to be better.
+                let is_null_window_move = context.principal_variation_search
+                    && best_move.is_some()
+                    && local_alpha > Score::MIN;
+
+                let null_window = SearchScores {
+                    alpha: -(local_alpha + Score::from(1)),
+                    beta: -local_alpha,
                 };
 
-                feedback.update(current_depth, searched_moves, branch.score.into());
+                context.extensions += extension;
+                let parent_pieces = std::mem::replace(&mut context.pieces, pieces);
 
-                #[cfg(feature = "verbose-search")]
-                {
-                    let _ = writeln!(
+                let mut null_window_result = None;
+
+                if is_late_move {
+                    let reduced_result = self.get_best_move_recursive_alpha_beta(
+                        current_depth + 1,
+                        child_max_depth - 1,
+                        null_window,
+                        context,
+                        stop_signal,
                         feedback,
-                        "{}{{\"{}\": \"{} {:+} α: {}, β: {}\"{}",
-                        indent(current_depth),
-                        player,
-                        mv,
-                        branch.score,
-                        local_alpha,
-                        scores.beta,
-                        if !is_leaf_node { ", \"s\": [" } else { "}," },
                     );
+                    let improves_alpha = reduced_result
+                        .branch
+                        .as_ref()
+                        .is_none_or(|branch| -branch.score > local_alpha);
+
+                    if reduced_result.stopped || !improves_alpha {
+                        null_window_result = Some(reduced_result);
+                    }
                 }
 
-                // Recursion
-                if threefold_repetition {
-                    // Enforce draw
-                    branch.score = Score::stalemate();
-                    branch.result = Some(GameResult::Draw);
-                } else if !is_leaf_node {
-                    let mut search_result = recursive_game.get_best_move_recursive_alpha_beta(
+                if null_window_result.is_none() && is_null_window_move {
+                    let pvs_result = self.get_best_move_recursive_alpha_beta(
                         current_depth + 1,
-                        max_depth,
+                        child_max_depth,
+                        null_window,
+                        context,
+                        stop_signal,
+                        feedback,
+                    );
+                    let is_inside_window = pvs_result.branch.as_ref().is_none_or(|branch| {
+                        -branch.score > local_alpha && -branch.score < scores.beta
+                    });
+
+                    if pvs_result.stopped || !is_inside_window {
+                        null_window_result = Some(pvs_result);
+                    }
+                }
+
+                let mut search_result = match null_window_result {
+                    Some(null_window_result) => null_window_result,
+                    None => self.get_best_move_recursive_alpha_beta(
+                        current_depth + 1,
+                        child_max_depth,
                         // beta becomes the alpha of the other player, and viceversa
                         SearchScores {
-                            parent: branch.score,
                             alpha: -scores.beta,
                             beta: -local_alpha,
                         },
-                        history,
+                        context,
                         stop_signal,
                         feedback,
-                    );
+                    ),
+                };
 
-                    let next_moves_opt = &mut search_result.branch;
-                    stopped = search_result.stopped;
+                context.extensions -= extension;
+                context.pieces = parent_pieces;
 
-                    let is_check_mate = if next_moves_opt.is_none() {
-                        // check or stale mate?
-                        let enemy_player_king_position =
-                            recursive_game.as_ref().board().find_king(&!player);
+                let next_moves_opt = &mut search_result.branch;
+                stopped = search_result.stopped;
 
-                        recursive_game
-                            .as_ref()
-                            .board()
-                            .is_piece_unsafe(&enemy_player_king_position)
-                    } else {
-                        false
-                    };
+                // check or stale mate?
+                let is_check_mate = next_moves_opt.is_none() && gives_check;
 
-                    #[cfg(feature = "verbose-search")]
-                    {
-                        let _ = writeln!(
-                            feedback,
-                            "{}{{\"best child\": \"{}\"}},",
-                            indent(current_depth + 1),
-                            next_moves_opt
-                                .as_ref()
-                                .map_or("<mate>".to_string(), |sub_branch| {
-                                    format!("{}", sub_branch)
-                                })
-                        );
-                    }
+                #[cfg(feature = "verbose-search")]
+                {
+                    let _ = writeln!(
+                        feedback,
+                        "{}{{\"best child\": \"{}\"}},",
+                        indent(current_depth + 1),
+                        next_moves_opt
+                            .as_ref()
+                            .map_or("<mate>".to_string(), |sub_branch| {
+                                format!("{}", sub_branch)
+                            })
+                    );
+                }
 
-                    if let Some(next_moves) = next_moves_opt {
-                        branch.moves.append(&mut next_moves.moves);
-                        branch.score = -next_moves.score; // notice the score of the child branch is negated
-                        branch.searched = next_moves.searched;
-                        branch.result = next_moves.result;
-                    } else if is_check_mate {
-                        branch.score = branch.score + Score::piece_value(PieceType::King);
-                        branch.result = Some(GameResult::Win(player));
-                    } else {
-                        // Stalemate
-                        branch.score = Score::stalemate();
-                        branch.result = Some(GameResult::Draw);
-                    }
+                if let Some(next_moves) = next_moves_opt {
+                    branch.moves.append(&mut next_moves.moves);
+                    branch.score = -next_moves.score; // notice the score of the child branch is negated
+                    branch.searched = next_moves.searched;
+                    branch.result = next_moves.result;
+                } else if is_check_mate {
+                    // Faster mates score higher
+                    branch.score = Score::mate_in(current_depth + 1);
+                    branch.result = Some(GameResult::Win(player));
+                } else {
+                    // Stalemate
+                    branch.score = Score::stalemate();
+                    branch.result = Some(GameResult::Draw);
+                }
 
-                    searched_moves += branch.searched;
+                searched_moves += branch.searched;
 
-                    #[cfg(feature = "verbose-search")]
-                    {
-                        let _ = writeln!(feedback, "{}],", indent(current_depth));
-                    }
+                #[cfg(feature = "verbose-search")]
+                {
+                    let _ = writeln!(feedback, "{}],", indent(current_depth));
                 }
+            } else {
+                // Leaf node: resolve the pending captures before scoring the move
+                let quiescence_result = self.get_quiescence_score(
+                    current_depth + 1,
+                    SearchScores {
+                        alpha: -scores.beta,
+                        beta: -local_alpha,
+                    },
+                    context,
+                    stop_signal,
+                );
+
+                branch.score = -quiescence_result.score;
+                branch.searched = quiescence_result.searched;
+                stopped = quiescence_result.stopped;
+
+                searched_moves += branch.searched;
+            }
 
-                history.pop().unwrap();
+            context.history.pop().unwrap();
+            self.undo_move(undo);
 
-                match &best_move {
-                    Some(current_best_move) => {
-                        if branch.score > current_best_move.score
-                            || (branch.score == current_best_move.score
-                                && branch.moves.len() < current_best_move.moves.len())
-                        {
-                            #[cfg(feature = "verbose-search")]
-                            {
-                                let _ = writeln!(
-                                    feedback,
-                                    "{}{{\"new best move\": \"{} > {}\"}},",
-                                    indent(current_depth),
-                                    branch,
-                                    current_best_move,
-                                );
-                            }
-                            best_move = Some(branch);
-                        }
-                    }
-                    None => {
+            // The endgame tablebases choose the root move, unless the search has found a mate
+            if let Some(tablebase_move) = context
+                .root_tablebase_scores
+                .iter()
+                .find(|weighted_move| current_depth == 0 && weighted_move.mv == move_action)
+            {
+                if !branch.score.is_mate() {
+                    branch.score = tablebase_move.score;
+                }
+            }
+
+            match &best_move {
+                Some(current_best_move) => {
+                    // Fail-hard cutoffs below score exactly the bound, so ties keep the first move
+                    if branch.score > current_best_move.score {
                         #[cfg(feature = "verbose-search")]
                         {
                             let _ = writeln!(
                                 feedback,
-                                "{}{{\"new best move\": \"{}\"}},",
+                                "{}{{\"new best move\": \"{} > {}\"}},",
                                 indent(current_depth),
                                 branch,
+                                current_best_move,
                             );
                         }
                         best_move = Some(branch);
                     }
-                };
+                }
+                None => {
+                    #[cfg(feature = "verbose-search")]
+                    {
+                        let _ = writeln!(
+                            feedback,
+                            "{}{{\"new best move\": \"{}\"}},",
+                            indent(current_depth),
+                            branch,
+                        );
+                    }
+                    best_move = Some(branch);
+                }
+            };
 
-                if let Some(best_move_score) = best_move.as_ref().map(|branch| branch.score) {
-                    if best_move_score >= scores.beta {
-                        // Fail hard beta cutoff
+            if let Some(best_move_score) = best_move.as_ref().map(|branch| branch.score) {
+                if best_move_score >= scores.beta {
+                    // Fail hard beta cutoff
 
-                        #[cfg(feature = "verbose-search")]
-                        {
-                            let _ = writeln!(
-                                feedback,
-                                "{}{{\"β cutoff\": \"{} >= {}\"}},",
-                                indent(current_depth),
-                                best_move_score,
-                                scores.beta
-                            );
-                        }
+                    #[cfg(feature = "verbose-search")]
+                    {
+                        let _ = writeln!(
+                            feedback,
+                            "{}{{\"β cutoff\": \"{} >= {}\"}},",
+                            indent(current_depth),
+                            best_move_score,
+                            scores.beta
+                        );
+                    }
 
-                        cutoff = true;
+                    if is_quiet_move {
+                        context.move_ordering.add_cutoff(
+                            &player,
+                            &possible_move,
+                            current_depth,
+                            remaining_depth,
+                        );
                     }
 
-                    // This will be the beta of the next recursion
-                    local_alpha = best_move_score;
+                    cutoff = true;
                 }
 
-                if stopped || cutoff {
-                    break 'main_loop;
-                }
-            } // possible moves loop
-        } // main loop
+                // This will be the beta of the next recursion
+                local_alpha = best_move_score;
+            }
+
+            if stopped || cutoff {
+                break;
+            }
+        } // possible moves loop
 
         if let Some(best_move) = best_move.as_mut() {
             best_move.searched = searched_moves;
+
+            if let (Some(hash), false) = (hash, stopped || is_partial_root) {
+                let bound = if best_move.score >= scores.beta {
+                    Bound::Lower
+                } else if best_move.score <= scores.alpha {
+                    Bound::Upper
+                } else {
+                    Bound::Exact
+                };
+
+                context.transposition_table.insert(TranspositionEntry {
+                    hash,
+                    depth: remaining_depth,
+                    bound,
+                    score: score_to_table(best_move.score, current_depth),
+                    best_move: best_move.moves.first().map(|mv| PackedMove::from(mv.mv)),
+                    result: best_move.result,
+                });
+            }
         }
 
         SearchResult {
@@ -609,16 +957,318 @@ trait GamePrivate<B: Board + SafetyChecks>: PlayableGame<B> + ModifiableGame<B>
         }
     }
 
+    // Search only captures and promotions until the position is quiet, to avoid the horizon
+    // effect at the leaf nodes
+    fn get_quiescence_score(
+        &mut self,
+        current_depth: u32,
+        scores: SearchScores,
+        context: &mut SearchContext,
+        stop_signal: &mut impl HasStopSignal,
+    ) -> QuiescenceResult {
+        let board = self.board();
+        let player = self.player();
+
+        let check_masks = CheckMasks::new(board, &player);
+        // A player in check cannot decline to move, so all the evasions are searched, unless the
+        // checks have gone on for too long
+        let search_evasions = check_masks.is_check() && current_depth < MAX_QUIESCENCE_PLIES;
+
+        let mut result = QuiescenceResult {
+            // Checkmated if there are no evasions
+            score: -Score::mate_in(current_depth),
+            searched: 0,
+            stopped: false,
+        };
+        let mut local_alpha = scores.alpha;
+
+        if !search_evasions {
+            // Stand pat: the player to move can always decline to capture
+            let stand_pat = evaluate(self.as_ref());
+            result.score = stand_pat;
+
+            if stand_pat >= scores.beta {
+                return result;
+            }
+
+            local_alpha = local_alpha.max(stand_pat);
+        }
+
+        let mut moves = self
+            .board_iter()
+            .only_player(player)
+            .flat_map(|position| self.get_possible_moves_no_checks(position))
+            .filter(|mv| {
+                (search_evasions || !is_quiet(board, mv)) && check_masks.is_legal(board, mv)
+            })
+            .collect::<Vec<PackedMove>>();
+
+        moves.sort_by_cached_key(|mv| std::cmp::Reverse(mvv_lva(board, mv)));
+
+        for possible_move in moves {
+            if stop_signal.stop() {
+                result.stopped = true;
+                break;
+            }
+
+            let move_action = MoveAction::from(possible_move);
+            let Ok(undo) = PlayableGame::do_move_no_checks(self, &move_action) else {
+                continue;
+            };
+
+            result.searched += 1;
+            context.nodes += 1;
+            context.selective_depth = context.selective_depth.max(current_depth + 1);
+
+            let child_result = self.get_quiescence_score(
+                current_depth + 1,
+                SearchScores {
+                    alpha: -scores.beta,
+                    beta: -local_alpha,
+                },
+                context,
+                stop_signal,
+            );
+
+            self.undo_move(undo);
+
+            let score = -child_result.score;
+            result.searched += child_result.searched;
+
+            if score > result.score {
+                result.score = score;
+            }
+
+            if child_result.stopped {
+                result.stopped = true;
+                break;
+            }
+
+            if score >= scores.beta {
+                break;
+            }
+
+            local_alpha = local_alpha.max(score);
+        }
+
+        result
+    }
+
+    // Aspiration windows: search the root with a narrow window around the expected score, which
+    // cuts off more branches, and widen the window while the score falls outside of it
+    fn get_best_move_aspiration(
+        &mut self,
+        max_depth: u32,
+        expected_score: Option<Score>,
+        context: &mut SearchContext,
+        stop_signal: &mut impl HasStopSignal,
+        feedback: &mut impl SearchFeedback,
+    ) -> SearchResult {
+        // Mate scores are too far from the rest for a window to be useful
+        let Some(expected_score) = expected_score.filter(|score| !score.is_mate()) else {
+            return self.get_best_move_recursive_alpha_beta(
+                0,
+                max_depth,
+                SearchScores::default(),
+                context,
+                stop_signal,
+                feedback,
+            );
+        };
+
+        let mut window_below = ASPIRATION_WINDOW;
+        let mut window_above = ASPIRATION_WINDOW;
+        let mut searched_moves: u32 = 0;
+
+        loop {
+            let alpha = if window_below > MAX_ASPIRATION_WINDOW {
+                Score::MIN
+            } else {
+                expected_score - Score::from(window_below)
+            };
+            let beta = if window_above > MAX_ASPIRATION_WINDOW {
+                Score::MAX
+            } else {
+                expected_score + Score::from(window_above)
+            };
+
+            let mut search_result = self.get_best_move_recursive_alpha_beta(
+                0,
+                max_depth,
+                SearchScores { alpha, beta },
+                context,
+                stop_signal,
+                feedback,
+            );
+
+            let Some(branch) = search_result.branch.as_mut() else {
+                return search_result;
+            };
+
+            // The moves of the failed searches are also counted
+            searched_moves += branch.searched;
+            branch.searched = searched_moves;
+
+            let fails_low = branch.score <= alpha && alpha > Score::MIN;
+            let fails_high = branch.score >= beta && beta < Score::MAX;
+
+            if search_result.stopped || !(fails_low || fails_high) {
+                return search_result;
+            }
+
+            if fails_low {
+                window_below *= 2;
+            } else {
+                window_above *= 2;
+            }
+        }
+    }
+
+    // Iterative deepening: search with increasing depth and keep the best branches of the
+    // last completed iteration, so that an interrupted search still returns a sound move.
+    // Each iteration searches the best `lines` moves of the root position, best first.
+    fn iterative_deepening(
+        &mut self,
+        first_depth: u32,
+        lines: usize,
+        limits: &SearchLimits,
+        context: &mut SearchContext,
+        stop_signal: &mut impl HasStopSignal,
+        feedback: &mut impl SearchFeedback,
+    ) -> Vec<Branch> {
+        let mut best_branches: Vec<Branch> = Vec::new();
+        let mut searched_moves: u32 = 0;
+
+        'iterations: for max_depth in first_depth..=limits.max_depth() {
+            let mut branches: Vec<Branch> = Vec::new();
+
+            context.selective_depth = 0;
+            context.excluded_root_moves.clear();
+
+            while branches.len() < lines {
+                // The score of the same line in the previous iteration
+                let expected_score = best_branches.get(branches.len()).map(|branch| branch.score);
+
+                let search_result = self.get_best_move_aspiration(
+                    max_depth,
+                    expected_score,
+                    context,
+                    stop_signal,
+                    feedback,
+                );
+
+                if search_result.stopped {
+                    // The branches of an interrupted iteration are only used if there is
+                    // nothing else
+                    if best_branches.is_empty() {
+                        best_branches.extend(branches);
+                        best_branches.extend(search_result.branch);
+                    }
+                    break 'iterations;
+                }
+
+                // No more legal moves
+                let Some(mut branch) = search_result.branch else {
+                    break;
+                };
+
+                self.complete_line(&mut branch, max_depth, context.transposition_table);
+
+                searched_moves += branch.searched;
+                branch.searched = searched_moves;
+
+                if let Some(first_move) = branch.moves.first() {
+                    context
+                        .excluded_root_moves
+                        .push(PackedMove::from(first_move.mv));
+                }
+                branches.push(branch);
+            }
+
+            // No legal moves: checkmate or stalemate
+            if branches.is_empty() {
+                break;
+            }
+
+            // Later lines can score better than earlier ones due to transpositions
+            branches.sort_by_key(|branch| std::cmp::Reverse(branch.score));
+
+            for (index, branch) in branches.iter().enumerate() {
+                feedback.iteration(&SearchIteration {
+                    depth: max_depth + 1,
+                    // Transposition table cutoffs can skip the deepest plies
+                    selective_depth: context.selective_depth.max(max_depth + 1),
+                    hash_full: context.transposition_table.hash_full(),
+                    nodes: context.nodes,
+                    line: index + 1,
+                    branch,
+                });
+            }
+
+            let mate_found =
+                limits.mate.is_some() && branches[0].result == Some(GameResult::Win(self.player()));
+
+            best_branches = branches;
+
+            if mate_found {
+                break;
+            }
+        }
+
+        best_branches
+    }
+
+    // A transposition table cutoff ends the line of the branch early: continue it with the best
+    // moves stored for the positions that follow, up to the depth of the search or, for a mate,
+    // up to the mate, which can be further away when found through the table
+    fn complete_line(
+        &self,
+        branch: &mut Branch,
+        max_depth: u32,
+        transposition_table: &TranspositionTable,
+    ) {
+        let plies = branch.score.mate_plies().unwrap_or(max_depth + 1);
+        let mut game = self.as_ref().clone();
+
+        for weighted_move in &branch.moves {
+            if !PlayableGame::do_move_with_checks(&mut game, &weighted_move.mv) {
+                return;
+            }
+        }
+
+        while branch.moves.len() < plies as usize {
+            let Some(mv) = game
+                .cached_hash()
+                .and_then(|hash| transposition_table.get(&hash))
+                .and_then(|entry| entry.best_move)
+                .map(MoveAction::from)
+            else {
+                break;
+            };
+
+            let score = move_score(game.board(), &mv);
+
+            // The entry can be of a different position with the same hash
+            if !PlayableGame::do_move_with_checks(&mut game, &mv) {
+                break;
+            }
+
+            branch.moves.push(WeightedMove { mv, score });
+        }
+    }
+
     fn get_best_move_shallow(&self) -> Option<Branch> {
-        self.get_best_move_recursive_alpha_beta(
-            0,
-            0,
-            SearchScores::default(),
-            &mut HashedHistory::default(),
-            &mut (),
-            &mut SilentSearchFeedback::default(),
-        )
-        .branch
+        self.as_ref()
+            .clone()
+            .get_best_move_recursive_alpha_beta(
+                0,
+                0,
+                SearchScores::default(),
+                &mut SearchContext::new(HashedHistory::default(), &TranspositionTable::new(0)),
+                &mut (),
+                &mut SilentSearchFeedback::default(),
+            )
+            .branch
     }
 
     fn get_possible_captures_of_position(&self, position: &Position) -> Vec<Position> {
@@ -649,7 +1299,7 @@ trait GamePrivate<B: Board + SafetyChecks>: PlayableGame<B> + ModifiableGame<B>
 }
 
 #[allow(private_bounds)]
-pub trait Game<B: Board + SafetyChecks>: GamePrivate<B> {
+pub trait Game<B: Board + SafetyChecks + PieceTargets>: GamePrivate<B> {
     fn get_possible_moves(&self, position: Position) -> Vec<MoveAction> {
         let Some(Piece { piece: _, player }) = self.board().at(&position) else {
             return vec![];
@@ -674,13 +1324,11 @@ pub trait Game<B: Board + SafetyChecks>: GamePrivate<B> {
     }
 
     fn get_all_possible_moves(&self) -> Vec<MoveAction> {
-        let mut moves: Vec<MoveAction> = vec![];
-
-        for piece_position in self.board_iter().only_player(self.player()) {
-            moves.extend(self.get_possible_moves_from_game(piece_position));
-        }
-
-        moves
+        let check_masks = CheckMasks::new(self.board(), &self.player());
+        self.get_legal_moves(&check_masks, self.board_iter().only_player(self.player()))
+            .into_iter()
+            .map(MoveAction::from)
+            .collect()
     }
 
     fn move_name(&self, move_action: &MoveAction) -> Result<String> {
@@ -809,24 +1457,126 @@ pub trait Game<B: Board + SafetyChecks>: GamePrivate<B> {
 
     fn get_best_move_recursive(
         &self,
-        search_depth: u32,
+        limits: &SearchLimits,
+        options: &SearchOptions,
         history: &GameHistory,
+        transposition_table: &TranspositionTable,
         stop_signal: &mut impl HasStopSignal,
         feedback: &mut impl SearchFeedback,
-    ) -> Option<Branch> {
-        let mut hashed_history = HashedHistory::from(history).ok()?;
-
-        hashed_history.reserve(search_depth as usize);
-
-        self.get_best_move_recursive_alpha_beta(
-            0,
-            search_depth,
-            SearchScores::default(),
-            &mut hashed_history,
+    ) -> Option<Branch>
+    where
+        B: Send,
+    {
+        self.get_best_branches_recursive(
+            limits,
+            options,
+            history,
+            transposition_table,
             stop_signal,
             feedback,
         )
-        .branch
+        .into_iter()
+        .next()
+    }
+
+    // Best `options.multi_pv` moves of the current position, each with its own branch, best
+    // first. There can be less if there are not enough legal moves.
+    fn get_best_branches_recursive(
+        &self,
+        limits: &SearchLimits,
+        options: &SearchOptions,
+        history: &GameHistory,
+        transposition_table: &TranspositionTable,
+        stop_signal: &mut impl HasStopSignal,
+        feedback: &mut impl SearchFeedback,
+    ) -> Vec<Branch>
+    where
+        B: Send,
+    {
+        let Ok(mut hashed_history) = HashedHistory::from(history) else {
+            return Vec::new();
+        };
+
+        hashed_history.reserve(limits.max_depth().min(MAX_RESERVED_PLIES) as usize + 1);
+
+        // The hash of the root position is propagated to the rest of the search tree
+        let mut root = self.as_ref().clone();
+        root.hash();
+
+        // The root moves are chosen by the endgame tablebases when they have the root position
+        let tablebase = options.tablebase.as_deref();
+        let root_tablebase_scores = tablebase
+            .and_then(|tablebase| {
+                let moves = root.get_all_possible_moves();
+                let root_dtz = tablebase.root_dtz(&root, &moves)?;
+
+                Some(
+                    std::iter::zip(moves, root_dtz)
+                        .map(|(mv, dtz)| WeightedMove {
+                            mv,
+                            score: tablebase_root_score(dtz),
+                        })
+                        .collect::<Vec<WeightedMove>>(),
+                )
+            })
+            .unwrap_or_default();
+        let pieces = self
+            .board_iter()
+            .filter(|position| self.board().at(position).is_some())
+            .count();
+
+        let stop_helpers = AtomicBool::new(false);
+
+        // Lazy SMP: helper threads search the same root and share their results through the
+        // transposition table, but only the result of the main thread is used.
+        std::thread::scope(|scope| {
+            for helper in 1..options.threads {
+                let mut helper_root = root.clone();
+                let mut helper_context = SearchContext {
+                    search_moves: &limits.search_moves,
+                    principal_variation_search: options.principal_variation_search,
+                    tablebase,
+                    pieces,
+                    root_tablebase_scores: &root_tablebase_scores,
+                    ..SearchContext::new(hashed_history.clone(), transposition_table)
+                };
+                let stop_helpers = &stop_helpers;
+
+                scope.spawn(move || {
+                    // Half of the helpers skip the first depth, so that threads diverge
+                    helper_root.iterative_deepening(
+                        (helper % 2) as u32,
+                        1,
+                        limits,
+                        &mut helper_context,
+                        &mut SharedStopSignal(stop_helpers),
+                        &mut SilentSearchFeedback::default(),
+                    );
+                });
+            }
+
+            let mut context = SearchContext {
+                search_moves: &limits.search_moves,
+                principal_variation_search: options.principal_variation_search,
+                tablebase,
+                pieces,
+                root_tablebase_scores: &root_tablebase_scores,
+                ..SearchContext::new(hashed_history, transposition_table)
+            };
+
+            let best_branches = root.iterative_deepening(
+                0,
+                options.multi_pv.max(1),
+                limits,
+                &mut context,
+                &mut NodesStopSignal::new(limits.nodes, stop_signal),
+                feedback,
+            );
+
+            stop_helpers.store(true, Ordering::Relaxed);
+
+            best_branches
+        })
     }
 
     fn get_possible_captures(&self) -> BoardCaptures {
@@ -841,19 +1591,110 @@ pub trait Game<B: Board + SafetyChecks>: GamePrivate<B> {
         board_captures
     }
 
+    // Static exchange evaluation: material won by the move after the captures and recaptures
+    // on its target square, when both players capture with their least valuable piece and stop
+    // as soon as it is no longer favorable. Pins are not taken into account.
+    fn static_exchange(&self, mv: &MoveAction) -> Score {
+        let mut board = self.board().clone();
+        let source = mv.mv.source;
+        let target = mv.mv.target;
+
+        let Some(moved_piece) = board.at(&source) else {
+            return Score::from(0);
+        };
+
+        let mut captured_value = board
+            .at(&target)
+            .map_or(Score::from(0), |piece| Score::piece_value(piece.piece));
+
+        // En passant: the captured pawn is not on the target square
+        if moved_piece.piece == PieceType::Pawn
+            && source.file != target.file
+            && board.at(&target).is_none()
+        {
+            captured_value = Score::piece_value(PieceType::Pawn);
+            board.update(&pos!(source.rank, target.file), None);
+        }
+
+        let mut piece_on_target = moved_piece;
+        if let MoveActionType::Promotion(promotion_piece) = mv.move_type {
+            piece_on_target.piece = promotion_piece.into();
+            captured_value = captured_value + Score::piece_value(piece_on_target.piece)
+                - Score::piece_value(PieceType::Pawn);
+        }
+
+        board.move_piece(&source, &target);
+        board.update(&target, Some(piece_on_target));
+
+        // Material won by each capture of the sequence, assuming that it is recaptured
+        let mut gains = vec![captured_value];
+
+        while let Some(attacker) = board.least_valuable_attacker(&target, &piece_on_target.player) {
+            let attacker_piece = board.at(&attacker).unwrap();
+
+            // The king cannot capture a defended piece
+            if attacker_piece.piece == PieceType::King
+                && board
+                    .least_valuable_attacker(&target, &attacker_piece.player)
+                    .is_some()
+            {
+                break;
+            }
+
+            gains.push(Score::piece_value(piece_on_target.piece) - *gains.last().unwrap());
+
+            board.move_piece(&attacker, &target);
+            piece_on_target = attacker_piece;
+        }
+
+        // Each player only captures if it does not lose material, starting from the last one
+        let last_gain = gains.pop().unwrap();
+        gains
+            .into_iter()
+            .rev()
+            .fold(last_gain, |next_gain, gain| -(-gain).max(next_gain))
+    }
+
     fn get_best_move_with_logger(
         &self,
-        search_depth: u32,
+        limits: &SearchLimits,
+        options: &SearchOptions,
         history: &GameHistory,
+        transposition_table: &TranspositionTable,
         stop_signal: &mut impl HasStopSignal,
         engine_feedback: &mut impl EngineFeedback,
-    ) -> GameMove {
+    ) -> GameMove
+    where
+        B: Send,
+    {
+        // Known openings are played from the book without searching
+        if let Some(book_move) = options
+            .book
+            .as_deref()
+            .and_then(|book| book.probe(self.as_ref(), options.book_policy))
+            .filter(|mv| limits.search_moves.is_empty() || limits.search_moves.contains(mv))
+        {
+            log!(
+                engine_feedback,
+                "Book move {}",
+                self.move_name(&book_move)
+                    .unwrap_or_else(|_| book_move.to_long_algebraic_string())
+            );
+            return GameMove::Normal(book_move);
+        }
+
         let player = self.player();
         let start_time = Instant::now();
         let mut feedback =
             PeriodicalSearchFeedback::new(std::time::Duration::from_millis(500), engine_feedback);
-        let best_branch =
-            self.get_best_move_recursive(search_depth, history, stop_signal, &mut feedback);
+        let best_branch = self.get_best_move_recursive(
+            limits,
+            options,
+            history,
+            transposition_table,
+            stop_signal,
+            &mut feedback,
+        );
         let duration = (Instant::now() - start_time).as_secs_f64();
 
         if best_branch.is_none() {
@@ -912,10 +1753,15 @@ pub trait Game<B: Board + SafetyChecks>: GamePrivate<B> {
         GameMove::Normal(**branch_moves.first().unwrap())
     }
 
-    fn get_best_move(&self, history: &GameHistory, search_depth: u32) -> GameMove {
+    fn get_best_move(&self, history: &GameHistory, search_depth: u32) -> GameMove
+    where
+        B: Send,
+    {
         self.get_best_move_with_logger(
-            search_depth,
+            &SearchLimits::from_depth(search_depth),
+            &SearchOptions::default(),
             history,
+            &TranspositionTable::default(),
             &mut (),
             &mut StdoutFeedback::default(),
         )
@@ -991,6 +1837,6 @@ pub trait Game<B: Board + SafetyChecks>: GamePrivate<B> {
     }
 }
 
-impl<B: Board + SafetyChecks> GamePrivate<B> for GameState<B> {}
+impl<B: Board + SafetyChecks + PieceTargets> GamePrivate<B> for GameState<B> {}
 
-impl<B: Board + SafetyChecks> Game<B> for GameState<B> {}
+impl<B: Board + SafetyChecks + PieceTargets> Game<B> for GameState<B> {}
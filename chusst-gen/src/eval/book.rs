@@ -0,0 +1,213 @@
+use super::check::SafetyChecks;
+use super::iter::PieceTargets;
+use super::Game;
+use crate::board::{Board, PieceType, Position};
+use crate::game::{GameState, ModifiableGame, Move, MoveAction, MoveActionType, PromotionPieces};
+
+use anyhow::{anyhow, bail, Result};
+use rand::Rng;
+
+use std::fmt;
+
+// Size of each entry of a Polyglot book: key, move, weight and learning data
+const ENTRY_SIZE: usize = 16;
+
+/// How a move is chosen when a position has several moves in the opening book.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub enum BookPolicy {
+    /// Always the move with the highest weight.
+    Best,
+    /// A random move, with a probability proportional to its weight.
+    #[default]
+    Weighted,
+}
+
+#[derive(Copy, Clone)]
+struct BookEntry {
+    key: u64,
+    mv: u16,
+    weight: u16,
+}
+
+impl BookEntry {
+    fn from_bytes(bytes: &[u8]) -> Self {
+        BookEntry {
+            key: u64::from_be_bytes(bytes[0..8].try_into().unwrap()),
+            mv: u16::from_be_bytes(bytes[8..10].try_into().unwrap()),
+            weight: u16::from_be_bytes(bytes[10..12].try_into().unwrap()),
+        }
+    }
+}
+
+// Position of the bits of a Polyglot move, from the least significant one:
+// target file, target rank, source file, source rank, each of 3 bits, and the promotion piece
+fn decode_move<B: Board>(game: &GameState<B>, mv: u16) -> MoveAction {
+    let field = |offset: u16| usize::from((mv >> offset) & 0x7);
+
+    let source = Position {
+        rank: field(9),
+        file: field(6),
+    };
+    let mut target = Position {
+        rank: field(3),
+        file: field(0),
+    };
+
+    // Castling is coded as the king capturing its own rook, from its initial square
+    let is_king_at_home = game.board().at(&source).is_some_and(|piece| {
+        piece.piece == PieceType::King && source.rank == B::home_rank(&piece.player)
+    });
+    if is_king_at_home && source.file == 4 && source.rank == target.rank {
+        match target.file {
+            7 => target.file = 6,
+            0 => target.file = 2,
+            _ => (),
+        }
+    }
+
+    let move_type = match field(12) {
+        1 => MoveActionType::Promotion(PromotionPieces::Knight),
+        2 => MoveActionType::Promotion(PromotionPieces::Bishop),
+        3 => MoveActionType::Promotion(PromotionPieces::Rook),
+        4 => MoveActionType::Promotion(PromotionPieces::Queen),
+        _ => MoveActionType::Normal,
+    };
+
+    MoveAction {
+        mv: Move { source, target },
+        move_type,
+    }
+}
+
+/// Opening book in the Polyglot format (`.bin` files), with weighted moves for known positions.
+pub struct OpeningBook {
+    // Sorted by key
+    entries: Vec<BookEntry>,
+}
+
+impl OpeningBook {
+    /// Reads the whole book from the file at `path`.
+    pub fn open(path: &str) -> Result<Self> {
+        let bytes = std::fs::read(path).map_err(|err| anyhow!("Cannot read {}: {}", path, err))?;
+
+        if bytes.len() % ENTRY_SIZE != 0 {
+            bail!("Invalid Polyglot book {}", path);
+        }
+
+        let mut entries = bytes
+            .chunks_exact(ENTRY_SIZE)
+            .map(BookEntry::from_bytes)
+            .collect::<Vec<BookEntry>>();
+
+        // Books are already sorted, but the order of the moves of each position is kept
+        entries.sort_by_key(|entry| entry.key);
+
+        Ok(OpeningBook { entries })
+    }
+
+    /// Number of moves in the book, of all positions.
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    // Legal moves of the position in the book, with their weights
+    fn moves<B: Board + SafetyChecks + PieceTargets>(
+        &self,
+        game: &GameState<B>,
+    ) -> Vec<(MoveAction, u32)> {
+        let key = u64::from(game.polyglot_hash());
+        let first = self.entries.partition_point(|entry| entry.key < key);
+        let legal_moves = game.get_all_possible_moves();
+
+        self.entries[first..]
+            .iter()
+            .take_while(|entry| entry.key == key)
+            .filter(|entry| entry.weight > 0)
+            .map(|entry| (decode_move(game, entry.mv), u32::from(entry.weight)))
+            .filter(|(mv, _)| legal_moves.contains(mv))
+            .collect()
+    }
+
+    /// Move of the book for the position, chosen with `policy`, or `None` if it is not in the
+    /// book.
+    pub(super) fn probe<B: Board + SafetyChecks + PieceTargets>(
+        &self,
+        game: &GameState<B>,
+        policy: BookPolicy,
+    ) -> Option<MoveAction> {
+        let moves = self.moves(game);
+
+        match policy {
+            // The first move wins the ties
+            BookPolicy::Best => moves
+                .iter()
+                .rev()
+                .max_by_key(|(_, weight)| *weight)
+                .map(|(mv, _)| *mv),
+            BookPolicy::Weighted => {
+                let total_weight = moves.iter().map(|(_, weight)| weight).sum::<u32>();
+                if total_weight == 0 {
+                    return None;
+                }
+
+                let mut choice = rand::thread_rng().gen_range(0..total_weight);
+                moves
+                    .into_iter()
+                    .find(|(_, weight)| {
+                        if choice < *weight {
+                            return true;
+                        }
+                        choice -= weight;
+                        false
+                    })
+                    .map(|(mv, _)| mv)
+            }
+        }
+    }
+}
+
+impl fmt::Debug for OpeningBook {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("OpeningBook")
+            .field("entries", &self.entries.len())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::board::SimpleBoard;
+    use crate::{mva, pos};
+
+    fn game_from_fen(fen: &str) -> GameState<SimpleBoard> {
+        GameState::try_from_fen(&fen.split_ascii_whitespace().collect::<Vec<&str>>()).unwrap()
+    }
+
+    // Polyglot move from the source to the target
+    fn encode_move(source: Position, target: Position) -> u16 {
+        ((source.rank << 9) | (source.file << 6) | (target.rank << 3) | target.file) as u16
+    }
+
+    #[test]
+    fn decode_castling() {
+        let game = game_from_fen("r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1");
+        let decode = |source, target| decode_move(&game, encode_move(source, target));
+        assert_eq!(decode(pos!(e1), pos!(h1)), mva!(e1 => g1));
+        assert_eq!(decode(pos!(e1), pos!(a1)), mva!(e1 => c1));
+        assert_eq!(decode(pos!(e8), pos!(h8)), mva!(e8 => g8));
+
+        // Only the king moves from its initial square are castling
+        let game = game_from_fen("8/8/8/8/R3K2R/8/8/8 w - - 0 1");
+        let decode = |source, target| decode_move(&game, encode_move(source, target));
+        assert_eq!(decode(pos!(e4), pos!(h4)), mva!(e4 => h4));
+        let game = game_from_fen("4K2R/8/8/8/8/8/8/4k3 w - - 0 1");
+        let decode = |source, target| decode_move(&game, encode_move(source, target));
+        assert_eq!(decode(pos!(e8), pos!(h8)), mva!(e8 => h8));
+        assert_eq!(decode(pos!(e1), pos!(h1)), mva!(e1 => h1));
+    }
+}
@@ -3,9 +3,10 @@ use crate::board::CompactBoard;
 #[cfg(feature = "bitboards")]
 use crate::board::{Bitboards, ModifiableBoard, PlayerBitboards};
 use crate::board::{
-    Board, Direction, Piece, PieceType, Player, Position, PositionIterator, SimpleBoard,
+    Board, Direction, Piece, PieceType, Player, Position, PositionIterator, SimpleBoard, Square,
 };
 use crate::eval::iter::dir;
+use crate::game::PackedMove;
 
 fn find_king(board: &impl Board, player: &Player) -> Position {
     match board.iter().find(|position| {
@@ -19,96 +20,113 @@ fn find_king(board: &impl Board, player: &Player) -> Position {
     }
 }
 
-fn is_position_unsafe(
+// Enemy piece of the player with the lowest value that attacks the position
+fn least_valuable_attacker(
     board: &impl Board,
     position: &Position,
     player: &Player,
     enemy_pawn_direction: i8,
-) -> bool {
-    let is_enemy_piece = |direction: Direction, piece: PieceType| {
-        board
-            .try_move(position, &direction)
-            .only_enemy_piece(*player, piece)
-            .next()
-            .is_some()
+) -> Option<Position> {
+    let enemy_piece = |directions: &[Direction], piece: PieceType| {
+        directions.iter().find_map(|direction| {
+            board
+                .try_move(position, direction)
+                .only_enemy_piece(*player, piece)
+                .next()
+        })
     };
 
     let enemy_in_direction = |direction: &Direction| {
-        board
+        let enemy_position = board
             .direction_iterator(position, direction)
             .first_non_empty()
             .only_enemy(*player)
-            .next()
-            .and_then(|pos| board.at(&pos))
-            .map(|piece| piece.piece)
+            .next()?;
+        Some((enemy_position, board.at(&enemy_position)?.piece))
+    };
+
+    let find_piece = |enemies: &[Option<(Position, PieceType)>], piece: PieceType| {
+        enemies
+            .iter()
+            .flatten()
+            .find(|(_, enemy_piece)| *enemy_piece == piece)
+            .map(|(enemy_position, _)| *enemy_position)
     };
 
     // 1. Pawns
-    if is_enemy_piece(dir!(enemy_pawn_direction, -1), PieceType::Pawn)
-        || is_enemy_piece(dir!(enemy_pawn_direction, 1), PieceType::Pawn)
-    {
-        return true;
+    if let Some(pawn) = enemy_piece(
+        &[
+            dir!(enemy_pawn_direction, -1),
+            dir!(enemy_pawn_direction, 1),
+        ],
+        PieceType::Pawn,
+    ) {
+        return Some(pawn);
     }
 
     // 2. Knights
-    if is_enemy_piece(dir!(-1, -2), PieceType::Knight)
-        || is_enemy_piece(dir!(-1, 2), PieceType::Knight)
-        || is_enemy_piece(dir!(-2, -1), PieceType::Knight)
-        || is_enemy_piece(dir!(-2, 1), PieceType::Knight)
-        || is_enemy_piece(dir!(2, -1), PieceType::Knight)
-        || is_enemy_piece(dir!(2, 1), PieceType::Knight)
-        || is_enemy_piece(dir!(1, -2), PieceType::Knight)
-        || is_enemy_piece(dir!(1, 2), PieceType::Knight)
-    {
-        return true;
+    if let Some(knight) = enemy_piece(
+        &[
+            dir!(-1, -2),
+            dir!(-1, 2),
+            dir!(-2, -1),
+            dir!(-2, 1),
+            dir!(2, -1),
+            dir!(2, 1),
+            dir!(1, -2),
+            dir!(1, 2),
+        ],
+        PieceType::Knight,
+    ) {
+        return Some(knight);
     }
 
-    // 3. Bishops or queens on diagonals
-    let bishop_or_queen = |direction: &Direction| {
-        matches!(
-            enemy_in_direction(direction),
-            Some(PieceType::Bishop) | Some(PieceType::Queen)
-        )
-    };
+    // 3. Bishops on diagonals
+    let diagonal_enemies = [dir!(-1, -1), dir!(-1, 1), dir!(1, -1), dir!(1, 1)]
+        .map(|direction| enemy_in_direction(&direction));
 
-    if bishop_or_queen(&dir!(-1, -1))
-        || bishop_or_queen(&dir!(-1, 1))
-        || bishop_or_queen(&dir!(1, -1))
-        || bishop_or_queen(&dir!(1, 1))
-    {
-        return true;
+    if let Some(bishop) = find_piece(&diagonal_enemies, PieceType::Bishop) {
+        return Some(bishop);
     }
 
-    // 4. Rooks or queens on files or ranks
-    let rook_or_queen = |direction: &Direction| {
-        matches!(
-            enemy_in_direction(direction),
-            Some(PieceType::Rook) | Some(PieceType::Queen)
-        )
-    };
+    // 4. Rooks on files or ranks
+    let straight_enemies = [dir!(0, -1), dir!(0, 1), dir!(-1, 0), dir!(1, 0)]
+        .map(|direction| enemy_in_direction(&direction));
 
-    if rook_or_queen(&dir!(0, -1))
-        || rook_or_queen(&dir!(0, 1))
-        || rook_or_queen(&dir!(-1, 0))
-        || rook_or_queen(&dir!(1, 0))
-    {
-        return true;
+    if let Some(rook) = find_piece(&straight_enemies, PieceType::Rook) {
+        return Some(rook);
     }
 
-    // 6. King
-    if is_enemy_piece(dir!(-1, -1), PieceType::King)
-        || is_enemy_piece(dir!(-1, 0), PieceType::King)
-        || is_enemy_piece(dir!(-1, 1), PieceType::King)
-        || is_enemy_piece(dir!(0, -1), PieceType::King)
-        || is_enemy_piece(dir!(0, 1), PieceType::King)
-        || is_enemy_piece(dir!(1, -1), PieceType::King)
-        || is_enemy_piece(dir!(1, 0), PieceType::King)
-        || is_enemy_piece(dir!(1, 1), PieceType::King)
+    // 5. Queens on diagonals, files or ranks
+    if let Some(queen) = find_piece(&diagonal_enemies, PieceType::Queen)
+        .or_else(|| find_piece(&straight_enemies, PieceType::Queen))
     {
-        return true;
+        return Some(queen);
     }
 
-    false
+    // 6. King
+    enemy_piece(
+        &[
+            dir!(-1, -1),
+            dir!(-1, 0),
+            dir!(-1, 1),
+            dir!(0, -1),
+            dir!(0, 1),
+            dir!(1, -1),
+            dir!(1, 0),
+            dir!(1, 1),
+        ],
+        PieceType::King,
+    )
+}
+
+fn is_position_unsafe(
+    board: &impl Board,
+    position: &Position,
+    player: &Player,
+    enemy_pawn_direction: i8,
+) -> bool {
+    least_valuable_attacker(board, position, player, enemy_pawn_direction).is_some()
 }
 
 fn is_position_unsafe_generic<B: Board>(board: &B, position: &Position, player: &Player) -> bool {
@@ -116,6 +134,47 @@ fn is_position_unsafe_generic<B: Board>(board: &B, position: &Position, player:
     is_position_unsafe(board, position, player, pd)
 }
 
+fn least_valuable_attacker_generic<B: Board>(
+    board: &B,
+    position: &Position,
+    player: &Player,
+) -> Option<Position> {
+    let pd = -B::pawn_progress_direction(&!*player);
+    least_valuable_attacker(board, position, player, pd)
+}
+
+// Without the king, a sliding piece that attacks it also attacks the positions behind it
+fn is_position_unsafe_without_king_generic<B: Board>(
+    board: &B,
+    position: &Position,
+    player: &Player,
+    king_position: &Position,
+) -> bool {
+    if is_position_unsafe_generic(board, position, player) {
+        return true;
+    }
+
+    let row_inc = king_position.rank as i8 - position.rank as i8;
+    let col_inc = king_position.file as i8 - position.file as i8;
+    if row_inc.abs() > 1 || col_inc.abs() > 1 || (row_inc == 0 && col_inc == 0) {
+        return false;
+    }
+
+    let slider = if row_inc == 0 || col_inc == 0 {
+        PieceType::Rook
+    } else {
+        PieceType::Bishop
+    };
+
+    board
+        .direction_iterator(king_position, &dir!(row_inc, col_inc))
+        .first_non_empty()
+        .only_enemy(*player)
+        .next()
+        .and_then(|attacker| board.at(&attacker))
+        .is_some_and(|attacker| attacker.piece == slider || attacker.piece == PieceType::Queen)
+}
+
 fn is_piece_unsafe(board: &impl Board, position: &Position) -> bool {
     let Some(Piece { piece: _, player }) = board.at(position) else {
         panic!("No piece at position {}:\n{}", position, board);
@@ -124,23 +183,188 @@ fn is_piece_unsafe(board: &impl Board, position: &Position) -> bool {
     is_position_unsafe_generic(board, position, &player)
 }
 
-pub fn only_empty_and_safe<B: Board>(
-    board: &B,
-    position: Option<Position>,
-    player: &Player,
-) -> Option<Position> {
-    let position_value = board.position_iter(&position?).only_empty().next()?;
-    if is_position_unsafe_generic(board, &position_value, player) {
-        None
-    } else {
-        position
+// Checks and pins of the king of a player, found once per position to know which moves are legal
+// without playing them
+pub struct CheckMasks {
+    player: Player,
+    king_position: Position,
+    checkers: usize,
+    // Positions that stop a single check: capturing the checker or blocking its attack
+    evasions: u64,
+    // Rays from the king to the pieces that pin a piece of the player, one per direction: the
+    // pinned piece can only move within its ray
+    pins: [u64; 8],
+}
+
+impl CheckMasks {
+    pub fn new<B: Board + SafetyChecks>(board: &B, player: &Player) -> Self {
+        let king_position = board.find_king(player);
+        let mut checkers = 0;
+        let mut evasions = 0;
+        let mut pins = [0; 8];
+
+        // Pawns and knights can only be captured
+        let pawn_direction = B::pawn_progress_direction(player);
+        let jumping_attackers = [
+            (PieceType::Pawn, dir!(pawn_direction, -1)),
+            (PieceType::Pawn, dir!(pawn_direction, 1)),
+            (PieceType::Knight, dir!(-1, -2)),
+            (PieceType::Knight, dir!(-1, 2)),
+            (PieceType::Knight, dir!(-2, -1)),
+            (PieceType::Knight, dir!(-2, 1)),
+            (PieceType::Knight, dir!(2, -1)),
+            (PieceType::Knight, dir!(2, 1)),
+            (PieceType::Knight, dir!(1, -2)),
+            (PieceType::Knight, dir!(1, 2)),
+        ];
+
+        for (piece, direction) in jumping_attackers {
+            if let Some(attacker) = board
+                .try_move(&king_position, &direction)
+                .only_enemy_piece(*player, piece)
+                .next()
+            {
+                checkers += 1;
+                evasions |= Square::from(attacker).mask();
+            }
+        }
+
+        // Sliding pieces can also be blocked, and a single piece in between is pinned
+        let sliding_attackers = [
+            (PieceType::Bishop, dir!(-1, -1)),
+            (PieceType::Bishop, dir!(-1, 1)),
+            (PieceType::Bishop, dir!(1, -1)),
+            (PieceType::Bishop, dir!(1, 1)),
+            (PieceType::Rook, dir!(0, -1)),
+            (PieceType::Rook, dir!(0, 1)),
+            (PieceType::Rook, dir!(-1, 0)),
+            (PieceType::Rook, dir!(1, 0)),
+        ];
+
+        for ((piece, direction), pin) in sliding_attackers.into_iter().zip(&mut pins) {
+            let mut ray = 0;
+            let mut pinned = None;
+
+            for position in board.direction_iterator(&king_position, &direction) {
+                ray |= Square::from(position).mask();
+
+                let Some(Piece {
+                    piece: found_piece,
+                    player: found_player,
+                }) = board.at(&position)
+                else {
+                    continue;
+                };
+
+                if found_player == *player {
+                    if pinned.is_some() {
+                        break;
+                    }
+                    pinned = Some(position);
+                    continue;
+                }
+
+                if found_piece == piece || found_piece == PieceType::Queen {
+                    match pinned {
+                        Some(_) => *pin = ray,
+                        None => {
+                            checkers += 1;
+                            evasions |= ray;
+                        }
+                    }
+                }
+                break;
+            }
+        }
+
+        CheckMasks {
+            player: *player,
+            king_position,
+            checkers,
+            evasions,
+            pins,
+        }
+    }
+
+    pub fn is_check(&self) -> bool {
+        self.checkers > 0
+    }
+
+    // Only for moves of the player from the move generator
+    pub fn is_legal<B: Board + SafetyChecks>(&self, board: &B, mv: &PackedMove) -> bool {
+        let source = mv.source();
+        let target = mv.target();
+
+        if source == self.king_position {
+            // The king cannot castle out of check nor through an attacked position
+            if source.file.abs_diff(target.file) == 2 {
+                let passed_position = Position {
+                    rank: source.rank,
+                    file: (source.file + target.file) / 2,
+                };
+                if self.is_check() || board.is_position_unsafe(&passed_position, &self.player) {
+                    return false;
+                }
+            }
+
+            return !board.is_position_unsafe_without_king(
+                &target,
+                &self.player,
+                &self.king_position,
+            );
+        }
+
+        // Only the king can move out of a double check
+        if self.checkers > 1 {
+            return false;
+        }
+
+        let is_en_passant = source.file != target.file
+            && board.at(&target).is_none()
+            && board
+                .at(&source)
+                .is_some_and(|piece| piece.piece == PieceType::Pawn);
+
+        // Both pawns leave the rank, which can expose the king: play it to find out
+        if is_en_passant {
+            let mut board = board.clone();
+            board.move_piece(&source, &target);
+            board.update(
+                &Position {
+                    rank: source.rank,
+                    file: target.file,
+                },
+                None,
+            );
+            return !board.is_position_unsafe(&self.king_position, &self.player);
+        }
+
+        let target_mask = Square::from(target).mask();
+        let stops_check = !self.is_check() || self.evasions & target_mask != 0;
+        let source_mask = Square::from(source).mask();
+        let keeps_pin = self
+            .pins
+            .iter()
+            .find(|ray| *ray & source_mask != 0)
+            .is_none_or(|ray| ray & target_mask != 0);
+
+        stops_check && keeps_pin
     }
 }
 
 pub trait SafetyChecks {
     fn find_king(&self, player: &Player) -> Position;
     fn is_position_unsafe(&self, position: &Position, player: &Player) -> bool;
+    // As if the king of the player was not on the board, to find where the king can move
+    fn is_position_unsafe_without_king(
+        &self,
+        position: &Position,
+        player: &Player,
+        king_position: &Position,
+    ) -> bool;
     fn is_piece_unsafe(&self, position: &Position) -> bool;
+    // Enemy piece of the player with the lowest value that attacks the position
+    fn least_valuable_attacker(&self, position: &Position, player: &Player) -> Option<Position>;
 }
 
 impl SafetyChecks for SimpleBoard {
@@ -152,9 +376,22 @@ impl SafetyChecks for SimpleBoard {
         is_position_unsafe_generic(self, position, player)
     }
 
+    fn is_position_unsafe_without_king(
+        &self,
+        position: &Position,
+        player: &Player,
+        king_position: &Position,
+    ) -> bool {
+        is_position_unsafe_without_king_generic(self, position, player, king_position)
+    }
+
     fn is_piece_unsafe(&self, position: &Position) -> bool {
         is_piece_unsafe(self, position)
     }
+
+    fn least_valuable_attacker(&self, position: &Position, player: &Player) -> Option<Position> {
+        least_valuable_attacker_generic(self, position, player)
+    }
 }
 
 #[cfg(feature = "compact-board")]
@@ -167,9 +404,22 @@ impl SafetyChecks for CompactBoard {
         is_position_unsafe_generic(self, position, player)
     }
 
+    fn is_position_unsafe_without_king(
+        &self,
+        position: &Position,
+        player: &Player,
+        king_position: &Position,
+    ) -> bool {
+        is_position_unsafe_without_king_generic(self, position, player, king_position)
+    }
+
     fn is_piece_unsafe(&self, position: &Position) -> bool {
         is_piece_unsafe(self, position)
     }
+
+    fn least_valuable_attacker(&self, position: &Position, player: &Player) -> Option<Position> {
+        least_valuable_attacker_generic(self, position, player)
+    }
 }
 
 #[cfg(feature = "bitboards")]
@@ -186,99 +436,26 @@ impl SafetyChecks for Bitboards {
     }
 
     fn is_position_unsafe(&self, position: &Position, player: &Player) -> bool {
-        let player_bitboards = self.by_player(player);
-        let enemy_bitboards = self.by_player(&!*player);
-
-        let all_pieces_bitboard = player_bitboards.combined() | enemy_bitboards.combined();
-
-        let attacker_in_rank =
-            |attacker_position: &Position| attacker_position.rank == position.rank;
-
-        let attacker_in_file =
-            |attacker_position: &Position| attacker_position.file == position.file;
-
-        let attacker_in_diagonal = |attacker_position: &Option<Position>| match attacker_position {
-            Some(pos) => {
-                let rank_diff = (pos.rank as i8 - position.rank as i8).abs();
-                let file_diff = (pos.file as i8 - position.file as i8).abs();
-                rank_diff == file_diff
-            }
-            None => false,
-        };
-
-        let enemy_in_rank_or_file = |piece: &PieceType| {
-            let attacker_bitboard = enemy_bitboards.by_piece(piece);
-            for attacker_position in PlayerBitboards::into_iter(attacker_bitboard) {
-                if attacker_in_rank(&attacker_position) || attacker_in_file(&attacker_position) {
-                    let in_between_mask = PlayerBitboards::in_between(&attacker_position, position);
-                    if in_between_mask & all_pieces_bitboard == 0 {
-                        return true;
-                    }
-                }
-            }
-            false
-        };
-
-        let enemy_in_diagonal = |piece: &PieceType| {
-            let attacker_bitboard = enemy_bitboards.by_piece(piece);
-            for attacker_position in PlayerBitboards::into_iter(attacker_bitboard) {
-                if attacker_in_diagonal(&Some(attacker_position)) {
-                    let in_between_mask = PlayerBitboards::in_between(&attacker_position, position);
-                    if in_between_mask & all_pieces_bitboard == 0 {
-                        return true;
-                    }
-                }
-            }
-            false
-        };
-
-        let enemy_in_rank_file_or_diagonal = |piece: &PieceType| {
-            let attacker_bitboard = enemy_bitboards.by_piece(piece);
-            for attacker_position in PlayerBitboards::into_iter(attacker_bitboard) {
-                if attacker_in_rank(&attacker_position)
-                    || attacker_in_file(&attacker_position)
-                    || attacker_in_diagonal(&Some(attacker_position))
-                {
-                    let in_between_mask = PlayerBitboards::in_between(&attacker_position, position);
-                    if in_between_mask & all_pieces_bitboard == 0 {
-                        return true;
-                    }
-                }
-            }
-            false
-        };
-
-        // 1. Pawns
-        if enemy_bitboards.pawn_can_attack(position) {
-            return true;
-        }
-
-        // 2. Knights
-        if enemy_bitboards.knight_can_attack(position) {
-            return true;
-        }
-
-        // 3. Bishops
-        if enemy_in_diagonal(&PieceType::Bishop) {
-            return true;
-        }
-
-        // 4. Rooks
-        if enemy_in_rank_or_file(&PieceType::Rook) {
-            return true;
-        }
-
-        // 5. Queens
-        if enemy_in_rank_file_or_diagonal(&PieceType::Queen) {
-            return true;
-        }
-
-        // 6. King
-        if enemy_bitboards.king_can_attack(position) {
-            return true;
-        }
+        self.least_valuable_attacker(position, player).is_some()
+    }
 
-        false
+    fn is_position_unsafe_without_king(
+        &self,
+        position: &Position,
+        player: &Player,
+        king_position: &Position,
+    ) -> bool {
+        let enemy_bitboards = self.by_player(&!*player);
+        let occupancy = (self.by_player(player).combined() | enemy_bitboards.combined())
+            & !Square::from(king_position).mask();
+
+        enemy_bitboards.pawn_attackers(position)
+            | enemy_bitboards.knight_attackers(position)
+            | enemy_bitboards.queen_attackers(position, occupancy)
+            | enemy_bitboards.bishop_attackers(position, occupancy)
+            | enemy_bitboards.rook_attackers(position, occupancy)
+            | enemy_bitboards.king_attackers(position)
+            != 0
     }
 
     fn is_piece_unsafe(&self, position: &Position) -> bool {
@@ -287,4 +464,27 @@ impl SafetyChecks for Bitboards {
         };
         self.is_position_unsafe(position, &player)
     }
+
+    fn least_valuable_attacker(&self, position: &Position, player: &Player) -> Option<Position> {
+        let player_bitboards = self.by_player(player);
+        let enemy_bitboards = self.by_player(&!*player);
+
+        let occupancy = player_bitboards.combined() | enemy_bitboards.combined();
+
+        let first_attacker =
+            |attackers_bitboard| PlayerBitboards::into_iter(attackers_bitboard).next();
+
+        // 1. Pawns
+        first_attacker(enemy_bitboards.pawn_attackers(position))
+            // 2. Knights
+            .or_else(|| first_attacker(enemy_bitboards.knight_attackers(position)))
+            // 3. Bishops
+            .or_else(|| first_attacker(enemy_bitboards.bishop_attackers(position, occupancy)))
+            // 4. Rooks
+            .or_else(|| first_attacker(enemy_bitboards.rook_attackers(position, occupancy)))
+            // 5. Queens
+            .or_else(|| first_attacker(enemy_bitboards.queen_attackers(position, occupancy)))
+            // 6. King
+            .or_else(|| first_attacker(enemy_bitboards.king_attackers(position)))
+    }
 }
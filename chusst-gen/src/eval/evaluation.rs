@@ -0,0 +1,274 @@
+use super::Score;
+use crate::board::{Board, Piece, PieceType, Player, Position};
+use crate::game::{GameState, ModifiableGame};
+
+// Piece-square tables, from the point of view of white, with the 8th rank first so that they
+// can be read like a board. Black uses the same tables vertically mirrored.
+type PieceSquareTable = [[i32; 8]; 8];
+
+#[rustfmt::skip]
+const PAWN_MIDDLEGAME: PieceSquareTable = [
+    [  0,   0,   0,   0,   0,   0,   0,   0],
+    [ 50,  50,  50,  50,  50,  50,  50,  50],
+    [ 10,  10,  20,  30,  30,  20,  10,  10],
+    [  5,   5,  10,  25,  25,  10,   5,   5],
+    [  0,   0,   0,  20,  20,   0,   0,   0],
+    [  5,  -5, -10,   0,   0, -10,  -5,   5],
+    [  5,  10,  10, -20, -20,  10,  10,   5],
+    [  0,   0,   0,   0,   0,   0,   0,   0],
+];
+
+#[rustfmt::skip]
+const PAWN_ENDGAME: PieceSquareTable = [
+    [  0,   0,   0,   0,   0,   0,   0,   0],
+    [ 80,  80,  80,  80,  80,  80,  80,  80],
+    [ 50,  50,  50,  50,  50,  50,  50,  50],
+    [ 30,  30,  30,  30,  30,  30,  30,  30],
+    [ 20,  20,  20,  20,  20,  20,  20,  20],
+    [ 10,  10,  10,  10,  10,  10,  10,  10],
+    [ 10,  10,  10,  10,  10,  10,  10,  10],
+    [  0,   0,   0,   0,   0,   0,   0,   0],
+];
+
+#[rustfmt::skip]
+const KNIGHT: PieceSquareTable = [
+    [-50, -40, -30, -30, -30, -30, -40, -50],
+    [-40, -20,   0,   0,   0,   0, -20, -40],
+    [-30,   0,  10,  15,  15,  10,   0, -30],
+    [-30,   5,  15,  20,  20,  15,   5, -30],
+    [-30,   0,  15,  20,  20,  15,   0, -30],
+    [-30,   5,  10,  15,  15,  10,   5, -30],
+    [-40, -20,   0,   5,   5,   0, -20, -40],
+    [-50, -40, -30, -30, -30, -30, -40, -50],
+];
+
+#[rustfmt::skip]
+const BISHOP: PieceSquareTable = [
+    [-20, -10, -10, -10, -10, -10, -10, -20],
+    [-10,   0,   0,   0,   0,   0,   0, -10],
+    [-10,   0,   5,  10,  10,   5,   0, -10],
+    [-10,   5,   5,  10,  10,   5,   5, -10],
+    [-10,   0,  10,  10,  10,  10,   0, -10],
+    [-10,  10,  10,  10,  10,  10,  10, -10],
+    [-10,   5,   0,   0,   0,   0,   5, -10],
+    [-20, -10, -10, -10, -10, -10, -10, -20],
+];
+
+#[rustfmt::skip]
+const ROOK: PieceSquareTable = [
+    [  0,   0,   0,   0,   0,   0,   0,   0],
+    [  5,  10,  10,  10,  10,  10,  10,   5],
+    [ -5,   0,   0,   0,   0,   0,   0,  -5],
+    [ -5,   0,   0,   0,   0,   0,   0,  -5],
+    [ -5,   0,   0,   0,   0,   0,   0,  -5],
+    [ -5,   0,   0,   0,   0,   0,   0,  -5],
+    [ -5,   0,   0,   0,   0,   0,   0,  -5],
+    [  0,   0,   0,   5,   5,   0,   0,   0],
+];
+
+#[rustfmt::skip]
+const QUEEN: PieceSquareTable = [
+    [-20, -10, -10,  -5,  -5, -10, -10, -20],
+    [-10,   0,   0,   0,   0,   0,   0, -10],
+    [-10,   0,   5,   5,   5,   5,   0, -10],
+    [ -5,   0,   5,   5,   5,   5,   0,  -5],
+    [  0,   0,   5,   5,   5,   5,   0,  -5],
+    [-10,   5,   5,   5,   5,   5,   0, -10],
+    [-10,   0,   5,   0,   0,   0,   0, -10],
+    [-20, -10, -10,  -5,  -5, -10, -10, -20],
+];
+
+#[rustfmt::skip]
+const KING_MIDDLEGAME: PieceSquareTable = [
+    [-30, -40, -40, -50, -50, -40, -40, -30],
+    [-30, -40, -40, -50, -50, -40, -40, -30],
+    [-30, -40, -40, -50, -50, -40, -40, -30],
+    [-30, -40, -40, -50, -50, -40, -40, -30],
+    [-20, -30, -30, -40, -40, -30, -30, -20],
+    [-10, -20, -20, -20, -20, -20, -20, -10],
+    [ 20,  20,   0,   0,   0,   0,  20,  20],
+    [ 20,  30,  10,   0,   0,  10,  30,  20],
+];
+
+#[rustfmt::skip]
+const KING_ENDGAME: PieceSquareTable = [
+    [-50, -40, -30, -20, -20, -30, -40, -50],
+    [-30, -20, -10,   0,   0, -10, -20, -30],
+    [-30, -10,  20,  30,  30,  20, -10, -30],
+    [-30, -10,  30,  40,  40,  30, -10, -30],
+    [-30, -10,  30,  40,  40,  30, -10, -30],
+    [-30, -10,  20,  30,  30,  20, -10, -30],
+    [-30, -30,   0,   0,   0,   0, -30, -30],
+    [-50, -30, -30, -30, -30, -30, -30, -50],
+];
+
+// Game phase of the starting position, when all the pieces are on the board
+const MAX_PHASE: i32 = 24;
+
+const DOUBLED_PAWN_PENALTY: TaperedScore = TaperedScore { mg: 10, eg: 20 };
+const ISOLATED_PAWN_PENALTY: TaperedScore = TaperedScore { mg: 10, eg: 15 };
+// Bonus of a passed pawn depending on how many ranks it has advanced
+const PASSED_PAWN_BONUS: [i32; 8] = [0, 5, 10, 20, 35, 60, 100, 0];
+
+// Score of a position in the middlegame and in the endgame, interpolated by the game phase
+#[derive(Copy, Clone, Default)]
+struct TaperedScore {
+    mg: i32,
+    eg: i32,
+}
+
+impl std::ops::AddAssign for TaperedScore {
+    fn add_assign(&mut self, other: Self) {
+        self.mg += other.mg;
+        self.eg += other.eg;
+    }
+}
+
+impl std::ops::SubAssign for TaperedScore {
+    fn sub_assign(&mut self, other: Self) {
+        self.mg -= other.mg;
+        self.eg -= other.eg;
+    }
+}
+
+fn player_index(player: &Player) -> usize {
+    match player {
+        Player::White => 0,
+        Player::Black => 1,
+    }
+}
+
+fn piece_phase(piece: PieceType) -> i32 {
+    match piece {
+        PieceType::Knight | PieceType::Bishop => 1,
+        PieceType::Rook => 2,
+        PieceType::Queen => 4,
+        PieceType::Pawn | PieceType::King => 0,
+    }
+}
+
+fn piece_square_value(piece: &Piece, position: &Position) -> TaperedScore {
+    let row = match piece.player {
+        Player::White => 7 - position.rank,
+        Player::Black => position.rank,
+    };
+    let value = |table: &PieceSquareTable| table[row][position.file];
+
+    match piece.piece {
+        PieceType::Pawn => TaperedScore {
+            mg: value(&PAWN_MIDDLEGAME),
+            eg: value(&PAWN_ENDGAME),
+        },
+        PieceType::King => TaperedScore {
+            mg: value(&KING_MIDDLEGAME),
+            eg: value(&KING_ENDGAME),
+        },
+        _ => {
+            let table = match piece.piece {
+                PieceType::Knight => &KNIGHT,
+                PieceType::Bishop => &BISHOP,
+                PieceType::Rook => &ROOK,
+                _ => &QUEEN,
+            };
+            TaperedScore {
+                mg: value(table),
+                eg: value(table),
+            }
+        }
+    }
+}
+
+fn pawn_structure(pawns: &[Vec<Position>; 2], player: &Player) -> TaperedScore {
+    let own_pawns = &pawns[player_index(player)];
+    let enemy_pawns = &pawns[player_index(&!*player)];
+    let mut score = TaperedScore::default();
+
+    let mut pawns_per_file = [0u8; 8];
+    for pawn in own_pawns {
+        pawns_per_file[pawn.file] += 1;
+    }
+
+    let is_adjacent_file = |file: usize, other_file: usize| file.abs_diff(other_file) == 1;
+
+    for pawn in own_pawns {
+        if pawns_per_file[pawn.file] > 1 {
+            score -= DOUBLED_PAWN_PENALTY;
+        }
+
+        let isolated = !own_pawns
+            .iter()
+            .any(|other| is_adjacent_file(pawn.file, other.file));
+        if isolated {
+            score -= ISOLATED_PAWN_PENALTY;
+        }
+
+        // No enemy pawn ahead in the same or adjacent files
+        let passed = !enemy_pawns.iter().any(|enemy| {
+            let is_ahead = match player {
+                Player::White => enemy.rank > pawn.rank,
+                Player::Black => enemy.rank < pawn.rank,
+            };
+            is_ahead && enemy.file.abs_diff(pawn.file) <= 1
+        });
+        if passed {
+            let advanced_ranks = match player {
+                Player::White => pawn.rank,
+                Player::Black => 7 - pawn.rank,
+            };
+            let bonus = PASSED_PAWN_BONUS[advanced_ranks];
+            score += TaperedScore {
+                mg: bonus / 2,
+                eg: bonus,
+            };
+        }
+    }
+
+    score
+}
+
+/// Static evaluation of the position, in centipawns, from the point of view of the player to
+/// move: material, piece-square tables and pawn structure, tapered between the middlegame and
+/// the endgame.
+pub fn evaluate<B: Board>(game: &GameState<B>) -> Score {
+    let board = game.board();
+    let mut scores = [TaperedScore::default(); 2];
+    let mut pawns: [Vec<Position>; 2] = Default::default();
+    let mut phase = 0;
+
+    for position in Board::iter(board) {
+        let Some(piece) = board.at(&position) else {
+            continue;
+        };
+        let index = player_index(&piece.player);
+
+        if piece.piece != PieceType::King {
+            let material = i32::from(Score::piece_value(piece.piece));
+            scores[index] += TaperedScore {
+                mg: material,
+                eg: material,
+            };
+        }
+        scores[index] += piece_square_value(&piece, &position);
+
+        if piece.piece == PieceType::Pawn {
+            pawns[index].push(position);
+        }
+
+        phase += piece_phase(piece.piece);
+    }
+
+    for player in [Player::White, Player::Black] {
+        scores[player_index(&player)] += pawn_structure(&pawns, &player);
+    }
+
+    let player = game.player();
+    let own = scores[player_index(&player)];
+    let enemy = scores[player_index(&!player)];
+
+    // Promotions can make the phase higher than in the starting position
+    let phase = phase.min(MAX_PHASE);
+    let mg = own.mg - enemy.mg;
+    let eg = own.eg - enemy.eg;
+
+    Score::from((mg * phase + eg * (MAX_PHASE - phase)) / MAX_PHASE)
+}
@@ -1,8 +1,23 @@
-#[derive(Clone)]
+use super::Branch;
+use crate::game::MoveAction;
+
+use std::time::{Duration, Instant};
+
+// The depth, the score and the line are only known at the end of each iteration, and the
+// periodical updates of the iterations in progress only have the current move
+#[derive(Clone, Default)]
 pub struct EngineFeedbackMessage {
-    pub depth: u32, // in plies
-    pub nodes: u32,
-    pub score: i32, // in centipawns
+    pub depth: Option<u32>,           // in plies
+    pub selective_depth: Option<u32>, // in plies, including the quiescence search
+    pub nodes: u32,                   // since the start of the search
+    pub score: Option<i32>,           // in centipawns
+    pub mate: Option<i32>,            // in moves, negative if the engine is checkmated
+    pub time: Duration,               // since the start of the search
+    pub hash_full: Option<u32>,       // in permille
+    pub current_move: Option<MoveAction>,
+    pub current_move_number: Option<u32>, // 1 for the first move of the root position, etc.
+    pub principal_variation: Vec<MoveAction>,
+    pub multi_pv: Option<usize>, // 1 for the best move, 2 for the second best, etc.
 }
 
 pub struct EngineInfoMessage {
@@ -18,8 +33,23 @@ pub trait EngineFeedback: std::io::Write {
     fn send(&self, msg: EngineMessage);
 }
 
+// Result of a completed iteration of the iterative deepening search
+pub struct SearchIteration<'a> {
+    pub depth: u32,           // in plies
+    pub selective_depth: u32, // in plies, including the quiescence search
+    pub hash_full: u32,       // in permille
+    pub nodes: u32,           // since the start of the search
+    pub line: usize,          // 1 for the best move, 2 for the second best, etc.
+    pub branch: &'a Branch,
+}
+
 pub trait SearchFeedback: std::io::Write {
-    fn update(&mut self, depth: u32, nodes: u32, score: i32);
+    // Called for each searched move, with the moves searched since the start of the search
+    fn update(&mut self, nodes: u32);
+    // Called when a move of the root position starts to be searched, with its number
+    fn current_move(&mut self, mv: &MoveAction, move_number: u32);
+    // Called when an iteration of the iterative deepening search is completed
+    fn iteration(&mut self, iteration: &SearchIteration);
     fn info(&mut self, message: &str);
 }
 
@@ -27,7 +57,15 @@ pub trait SearchFeedback: std::io::Write {
 pub struct SilentSearchFeedback();
 
 impl SearchFeedback for SilentSearchFeedback {
-    fn update(&mut self, _depth: u32, _nodes: u32, _score: i32) {
+    fn update(&mut self, _nodes: u32) {
+        // do nothing
+    }
+
+    fn current_move(&mut self, _mv: &MoveAction, _move_number: u32) {
+        // do nothing
+    }
+
+    fn iteration(&mut self, _iteration: &SearchIteration) {
         // do nothing
     }
 
@@ -47,37 +85,68 @@ impl std::io::Write for SilentSearchFeedback {
 }
 
 pub struct PeriodicalSearchFeedback<'a> {
-    update_interval: std::time::Duration,
-    last_update: std::time::Instant,
+    update_interval: Duration,
+    start: Instant,
+    last_update: Instant,
+    current_move: Option<(MoveAction, u32)>,
     receiver: &'a mut dyn EngineFeedback,
 }
 
 impl<'a> PeriodicalSearchFeedback<'a> {
-    pub fn new(
-        update_interval: std::time::Duration,
-        receiver: &'a mut impl EngineFeedback,
-    ) -> Self {
+    pub fn new(update_interval: Duration, receiver: &'a mut impl EngineFeedback) -> Self {
+        let now = Instant::now();
         PeriodicalSearchFeedback {
             update_interval,
-            last_update: std::time::Instant::now(),
+            start: now,
+            last_update: now,
+            current_move: None,
             receiver,
         }
     }
 }
 
 impl<'a> SearchFeedback for PeriodicalSearchFeedback<'a> {
-    fn update(&mut self, depth: u32, nodes: u32, score: i32) {
-        let now = std::time::Instant::now();
+    fn update(&mut self, nodes: u32) {
+        let now = Instant::now();
 
         if now - self.last_update < self.update_interval {
             return;
         }
 
+        // The score of the move being searched is not known yet
         self.receiver
             .send(EngineMessage::SearchFeedback(EngineFeedbackMessage {
-                depth,
                 nodes,
-                score,
+                time: now - self.start,
+                current_move: self.current_move.map(|(mv, _)| mv),
+                current_move_number: self.current_move.map(|(_, move_number)| move_number),
+                ..Default::default()
+            }));
+
+        self.last_update = now;
+    }
+
+    fn current_move(&mut self, mv: &MoveAction, move_number: u32) {
+        self.current_move = Some((*mv, move_number));
+    }
+
+    fn iteration(&mut self, iteration: &SearchIteration) {
+        let now = Instant::now();
+
+        // Completed iterations are always reported, regardless of the update interval
+        self.receiver
+            .send(EngineMessage::SearchFeedback(EngineFeedbackMessage {
+                depth: Some(iteration.depth),
+                selective_depth: Some(iteration.selective_depth),
+                nodes: iteration.nodes,
+                score: Some(iteration.branch.score.into()),
+                mate: iteration.branch.score.mate_moves(),
+                time: now - self.start,
+                hash_full: Some(iteration.hash_full),
+                current_move: None,
+                current_move_number: None,
+                principal_variation: iteration.branch.moves.iter().map(|mv| mv.mv).collect(),
+                multi_pv: Some(iteration.line),
             }));
 
         self.last_update = now;
@@ -110,7 +179,15 @@ impl EngineFeedback for StdoutFeedback {
 }
 
 impl SearchFeedback for StdoutFeedback {
-    fn update(&mut self, _depth: u32, _nodes: u32, _score: i32) {
+    fn update(&mut self, _nodes: u32) {
+        // ignore
+    }
+
+    fn current_move(&mut self, _mv: &MoveAction, _move_number: u32) {
+        // ignore
+    }
+
+    fn iteration(&mut self, _iteration: &SearchIteration) {
         // ignore
     }
 
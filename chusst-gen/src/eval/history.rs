@@ -1,4 +1,4 @@
-use crate::game::{GameHash, GameHashBuilder, MoveAction, SimpleGame};
+use crate::game::{GameHash, GameHashBuilder, MoveAction, PackedMove, SimpleGame};
 use anyhow::{Context, Result};
 use std::collections::HashMap;
 
@@ -6,8 +6,9 @@ use super::Game;
 
 pub type GameHistory = Vec<MoveAction>;
 
+#[derive(Clone)]
 pub struct HashedHistory {
-    moves: Vec<(MoveAction, GameHash)>,
+    moves: Vec<(PackedMove, GameHash)>,
     hashes: HashMap<GameHash, Vec<usize>, GameHashBuilder>,
     // hashes: HashMap<GameHash, Vec<usize>>,
 }
@@ -34,7 +35,7 @@ impl HashedHistory {
 
         for mv in moves {
             let hash = game.hash();
-            history.push(*mv, hash);
+            history.push(PackedMove::from(*mv), hash);
             game.do_move(mv)
                 .context(format!("Invalid move {}", mv.mv))?;
         }
@@ -47,7 +48,7 @@ impl HashedHistory {
         self.hashes.reserve(additional);
     }
 
-    pub fn push(&mut self, mv: MoveAction, hash: GameHash) {
+    pub fn push(&mut self, mv: PackedMove, hash: GameHash) {
         self.moves.push((mv, hash));
         self.hashes
             .entry(hash)
@@ -55,7 +56,7 @@ impl HashedHistory {
             .push(self.moves.len() - 1);
     }
 
-    pub fn pop(&mut self) -> Result<MoveAction> {
+    pub fn pop(&mut self) -> Result<PackedMove> {
         let (mv, hash) = self.moves.pop().context("Empty")?;
         self.hashes
             .get_mut(&hash)
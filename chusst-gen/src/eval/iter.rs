@@ -1,8 +1,14 @@
+#[cfg(feature = "compact-board")]
+use crate::board::CompactBoard;
+#[cfg(feature = "bitboards")]
+use crate::board::{Bitboards, PlayerBitboards};
 use crate::board::{
     Board, Direction, IterableBoard, ModifiableBoard, Piece, PieceType, Player, Position,
-    PositionIterator,
+    PositionIterator, SimpleBoard,
 };
 use crate::eval::conditions::only_en_passant;
+#[cfg(feature = "bitboards")]
+use crate::game::MoveExtraInfo;
 use crate::game::{CastlingRights, GameState, ModifiableGame};
 
 #[macro_export]
@@ -269,18 +275,15 @@ macro_rules! positional_state {
 
 macro_rules! walk_state {
     ($self:ident, $dir:expr => $next_state:expr) => {{
-        let iter = if $self.iter.is_none() {
-            $self.iter = Some(Box::new(
+        let iter = $self.iter.get_or_insert_with(|| {
+            Box::new(
                 $self
                     .game_state
                     .game
                     .direction_iterator(&$self.game_state.position, &$dir)
                     .take_while_empty_until_enemy($self.player),
-            ));
-            $self.iter.as_mut().unwrap()
-        } else {
-            $self.iter.as_mut().unwrap()
-        };
+            )
+        });
 
         match iter.next() {
             Some(position) => Some(position),
@@ -512,3 +515,111 @@ impl<'a, B: Board> Iterator for KingIter<'a, B> {
         }
     }
 }
+
+// Move generation specific to each board representation
+pub trait PieceTargets: Board {
+    // Target positions of the piece at the position, without checking if the king is left in
+    // check nor if castling crosses attacked positions
+    fn piece_targets(
+        game: &GameState<Self>,
+        position: Position,
+    ) -> impl Iterator<Item = Position> + '_ {
+        piece_into_iter(game, position)
+    }
+}
+
+impl PieceTargets for SimpleBoard {}
+
+#[cfg(feature = "compact-board")]
+impl PieceTargets for CompactBoard {}
+
+#[cfg(feature = "bitboards")]
+impl PieceTargets for Bitboards {
+    fn piece_targets(
+        game: &GameState<Self>,
+        position: Position,
+    ) -> impl Iterator<Item = Position> + '_ {
+        let board = game.board();
+        let Some(Piece { piece, player }) = board.at(&position) else {
+            return PlayerBitboards::into_iter(0);
+        };
+
+        let player_pieces = board.by_player(&player).combined();
+        let enemy_pieces = board.by_player(&!player).combined();
+        let occupancy = player_pieces | enemy_pieces;
+
+        let targets = match piece {
+            PieceType::Pawn => {
+                let direction = Self::pawn_progress_direction(&player);
+                let forward = |position: &Position, ranks: i8| Position {
+                    rank: position
+                        .rank
+                        .wrapping_add_signed(isize::from(direction * ranks)),
+                    file: position.file,
+                };
+
+                let mut targets = 0;
+
+                let one_step = PlayerBitboards::position_mask(&forward(&position, 1));
+                if one_step & occupancy == 0 {
+                    targets |= one_step;
+
+                    let can_pass = match player {
+                        Player::White => position.rank == 1,
+                        Player::Black => position.rank == 6,
+                    };
+                    let two_steps = if can_pass {
+                        PlayerBitboards::position_mask(&forward(&position, 2))
+                    } else {
+                        0
+                    };
+                    targets |= two_steps & !occupancy;
+                }
+
+                let attacks = PlayerBitboards::pawn_attacks(&player, &position);
+                targets |= attacks & enemy_pieces;
+
+                // En passant, behind the enemy pawn that has just passed
+                if let Some(last_move) = game.last_move() {
+                    if last_move.info == MoveExtraInfo::Passed {
+                        targets |= attacks
+                            & PlayerBitboards::position_mask(&forward(&last_move.mv.target, 1));
+                    }
+                }
+
+                targets
+            }
+            PieceType::Knight => PlayerBitboards::knight_attacks(&position) & !player_pieces,
+            PieceType::Bishop => {
+                PlayerBitboards::bishop_attacks(&position, occupancy) & !player_pieces
+            }
+            PieceType::Rook => PlayerBitboards::rook_attacks(&position, occupancy) & !player_pieces,
+            PieceType::Queen => {
+                PlayerBitboards::queen_attacks(&position, occupancy) & !player_pieces
+            }
+            PieceType::King => {
+                let mut targets = PlayerBitboards::king_attacks(&position) & !player_pieces;
+
+                // The squares between the king and the rook must be empty
+                let rank = position.rank;
+                let castling_squares = |files: &[usize]| {
+                    files.iter().fold(0, |bitboard, file| {
+                        bitboard | PlayerBitboards::position_mask(&Position { rank, file: *file })
+                    })
+                };
+                if game.can_castle_kingside(player) && castling_squares(&[5, 6]) & occupancy == 0 {
+                    targets |= castling_squares(&[6]);
+                }
+                if game.can_castle_queenside(player)
+                    && castling_squares(&[1, 2, 3]) & occupancy == 0
+                {
+                    targets |= castling_squares(&[2]);
+                }
+
+                targets
+            }
+        };
+
+        PlayerBitboards::into_iter(targets)
+    }
+}
@@ -0,0 +1,73 @@
+use super::HasStopSignal;
+use crate::game::MoveAction;
+
+use std::sync::atomic::{AtomicBool, Ordering};
+
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SearchLimits {
+    // Maximum depth of the search tree, 0 being the moves of the current player
+    pub depth: u32,
+    // Maximum number of moves searched
+    pub nodes: Option<u32>,
+    // Search for a mate in this number of moves
+    pub mate: Option<u32>,
+    // Only these moves are searched in the current position, all of them if empty
+    pub search_moves: Vec<MoveAction>,
+}
+
+impl SearchLimits {
+    pub fn from_depth(depth: u32) -> Self {
+        SearchLimits {
+            depth,
+            ..Default::default()
+        }
+    }
+
+    pub(super) fn max_depth(&self) -> u32 {
+        match self.mate {
+            // A mate in N moves takes 2N - 1 plies, plus one to verify that there are no replies
+            Some(mate) => self.depth.min(mate.saturating_mul(2).saturating_sub(1)),
+            None => self.depth,
+        }
+    }
+}
+
+// Stop signal that fires after a number of nodes, or when the inner signal fires
+pub(super) struct NodesStopSignal<'a, S: HasStopSignal> {
+    nodes: u32,
+    max_nodes: Option<u32>,
+    inner: &'a mut S,
+}
+
+impl<'a, S: HasStopSignal> NodesStopSignal<'a, S> {
+    pub fn new(max_nodes: Option<u32>, inner: &'a mut S) -> Self {
+        NodesStopSignal {
+            nodes: 0,
+            max_nodes,
+            inner,
+        }
+    }
+}
+
+impl<S: HasStopSignal> HasStopSignal for NodesStopSignal<'_, S> {
+    fn stop(&mut self) -> bool {
+        // The stop signal is checked once per searched move
+        self.nodes += 1;
+
+        if self.inner.stop() {
+            return true;
+        }
+
+        self.max_nodes
+            .is_some_and(|max_nodes| self.nodes > max_nodes)
+    }
+}
+
+// Stop signal shared by several search threads, raised by another thread
+pub(super) struct SharedStopSignal<'a>(pub &'a AtomicBool);
+
+impl HasStopSignal for SharedStopSignal<'_> {
+    fn stop(&mut self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
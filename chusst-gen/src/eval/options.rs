@@ -0,0 +1,32 @@
+use super::{BookPolicy, OpeningBook, Tablebase};
+
+use std::sync::Arc;
+
+#[derive(Clone, Debug)]
+pub struct SearchOptions {
+    // Number of threads searching in parallel
+    pub threads: usize,
+    // Number of best moves of the current position that are searched, each with its own branch
+    pub multi_pv: usize,
+    // Search the moves after the first one with a null window, instead of plain alpha-beta
+    pub principal_variation_search: bool,
+    // Endgame tablebases that override the search in the positions with few pieces
+    pub tablebase: Option<Arc<Tablebase>>,
+    // Opening book whose moves are played without searching
+    pub book: Option<Arc<OpeningBook>>,
+    // How the move is chosen among the moves of the book
+    pub book_policy: BookPolicy,
+}
+
+impl Default for SearchOptions {
+    fn default() -> Self {
+        SearchOptions {
+            threads: 1,
+            multi_pv: 1,
+            principal_variation_search: false,
+            tablebase: None,
+            book: None,
+            book_policy: BookPolicy::default(),
+        }
+    }
+}
@@ -0,0 +1,146 @@
+use super::Score;
+use crate::board::{Board, PieceType, Player};
+use crate::game::PackedMove;
+
+// Sorting keys of each stage, from the first to be searched to the last
+const HASH_MOVE_KEY: i32 = i32::MAX;
+const CAPTURE_KEY: i32 = 2_000_000;
+const KILLER_MOVE_KEYS: [i32; KILLER_MOVES] = [1_500_000, 1_400_000];
+// History scores are halved when they reach this value, so that they always sort after killers
+const MAX_HISTORY_SCORE: u32 = 1_000_000;
+
+const KILLER_MOVES: usize = 2;
+
+fn history_index(player: &Player, mv: &PackedMove) -> usize {
+    let player_index = match player {
+        Player::White => 0,
+        Player::Black => 1,
+    };
+    (player_index * 64 + mv.source_square().index()) * 64 + mv.target_square().index()
+}
+
+// En passant is the only capture to an empty square
+fn is_en_passant(board: &impl Board, mv: &PackedMove) -> bool {
+    mv.source().file != mv.target().file
+        && board.at(&mv.target()).is_none()
+        && board
+            .at(&mv.source())
+            .is_some_and(|piece| piece.piece == PieceType::Pawn)
+}
+
+// Captures and promotions change the material, the rest of the moves are quiet
+pub(super) fn is_quiet(board: &impl Board, mv: &PackedMove) -> bool {
+    board.at(&mv.target()).is_none() && mv.promotion().is_none() && !is_en_passant(board, mv)
+}
+
+// Most valuable victim, least valuable attacker
+pub(super) fn mvv_lva(board: &impl Board, mv: &PackedMove) -> i32 {
+    let victim = match (board.at(&mv.target()), mv.promotion()) {
+        (Some(piece), _) => i32::from(Score::piece_value(piece.piece)),
+        (None, Some(promotion_piece)) => i32::from(Score::piece_value(promotion_piece.into())),
+        (None, None) if is_en_passant(board, mv) => i32::from(Score::piece_value(PieceType::Pawn)),
+        (None, None) => 0,
+    };
+    let attacker = board
+        .at(&mv.source())
+        .map_or(0, |piece| i32::from(Score::piece_value(piece.piece)) / 100);
+
+    victim * 10 - attacker
+}
+
+/// Heuristics to search first the moves that are more likely to cause a beta cutoff.
+pub(super) struct MoveOrdering {
+    // Quiet moves that caused a beta cutoff, per depth
+    killer_moves: Vec<[Option<PackedMove>; KILLER_MOVES]>,
+    // Accumulated beta cutoffs of quiet moves, per player, source and target square
+    history: Vec<u32>,
+}
+
+impl Default for MoveOrdering {
+    fn default() -> Self {
+        MoveOrdering {
+            killer_moves: Vec::new(),
+            history: vec![0; 2 * 64 * 64],
+        }
+    }
+}
+
+impl MoveOrdering {
+    fn sort_key(
+        &self,
+        board: &impl Board,
+        player: &Player,
+        mv: &PackedMove,
+        hash_move: Option<&PackedMove>,
+        killer_moves: &[Option<PackedMove>; KILLER_MOVES],
+    ) -> i32 {
+        if hash_move == Some(mv) {
+            return HASH_MOVE_KEY;
+        }
+
+        if !is_quiet(board, mv) {
+            return CAPTURE_KEY + mvv_lva(board, mv);
+        }
+
+        if let Some(killer_index) = killer_moves
+            .iter()
+            .position(|killer_move| killer_move.as_ref() == Some(mv))
+        {
+            return KILLER_MOVE_KEYS[killer_index];
+        }
+
+        // Always lower than MAX_HISTORY_SCORE
+        self.history[history_index(player, mv)] as i32
+    }
+
+    /// Sorts the moves of `player` at `depth`: hash move, captures by MVV-LVA, killer moves,
+    /// and quiet moves by the history heuristic.
+    pub fn sort(
+        &self,
+        board: &impl Board,
+        player: &Player,
+        moves: &mut [PackedMove],
+        hash_move: Option<&PackedMove>,
+        depth: u32,
+    ) {
+        let killer_moves = self
+            .killer_moves
+            .get(depth as usize)
+            .copied()
+            .unwrap_or_default();
+
+        moves.sort_by_cached_key(|mv| {
+            std::cmp::Reverse(self.sort_key(board, player, mv, hash_move, &killer_moves))
+        });
+    }
+
+    /// Records a quiet move that caused a beta cutoff at `depth`, with `remaining_depth` plies
+    /// searched below it.
+    pub fn add_cutoff(
+        &mut self,
+        player: &Player,
+        mv: &PackedMove,
+        depth: u32,
+        remaining_depth: u32,
+    ) {
+        let depth = depth as usize;
+        if self.killer_moves.len() <= depth {
+            self.killer_moves.resize(depth + 1, Default::default());
+        }
+
+        let killer_moves = &mut self.killer_moves[depth];
+        if killer_moves[0] != Some(*mv) {
+            killer_moves[1] = killer_moves[0];
+            killer_moves[0] = Some(*mv);
+        }
+
+        // Deeper cutoffs are more significant
+        let remaining_plies = remaining_depth + 1;
+        let history_score = &mut self.history[history_index(player, mv)];
+        *history_score += remaining_plies * remaining_plies;
+
+        if *history_score >= MAX_HISTORY_SCORE {
+            self.history.iter_mut().for_each(|score| *score /= 2);
+        }
+    }
+}
@@ -1,30 +1,21 @@
 use crate::board::Board;
 use crate::eval::Game;
-use crate::game::{GameState, ModifiableGame, MoveAction};
+use crate::game::{GameState, ModifiableGame, MoveAction, MoveUndo};
 use anyhow::Result;
 
 use super::check::SafetyChecks;
+use super::iter::PieceTargets;
 
 pub trait PlayableGame<B: Board>: ModifiableGame<B>
 where
-    B: SafetyChecks,
+    B: SafetyChecks + PieceTargets,
 {
     fn as_ref(&self) -> &GameState<B>;
     fn as_mut(&mut self) -> &mut GameState<B>;
 
-    fn clone_and_move(&self, mv: &MoveAction, reset_hash: bool) -> Result<GameState<B>> {
-        let mut new_game = if reset_hash {
-            self.as_ref().clone_unhashed()
-        } else {
-            self.as_ref().clone()
-        };
-        PlayableGame::do_move_no_checks(&mut new_game, mv)?;
-        Ok(new_game)
-    }
-
-    // This is slower than using eval::GamePrivate::clone_and_move_with_checks()
-    // because here we don't know if the move is legal, so we check against
-    // all possible legal moves.
+    // This is slower than playing the moves of eval::Game::get_all_possible_moves(),
+    // which are already legal, because here we don't know if the move is legal,
+    // so we check against all possible legal moves.
     fn do_move_with_checks(&mut self, move_action: &MoveAction) -> bool {
         let mv = &move_action.mv;
 
@@ -51,5 +42,5 @@ where
         PlayableGame::do_move_no_checks(self, move_action).is_ok()
     }
 
-    fn do_move_no_checks(&mut self, move_action: &MoveAction) -> Result<()>;
+    fn do_move_no_checks(&mut self, move_action: &MoveAction) -> Result<MoveUndo>;
 }
@@ -0,0 +1,825 @@
+mod table;
+
+pub(super) use self::table::Wdl;
+use self::table::{Material, PieceCode, Table, TableType, BLACK_PIECE};
+use super::check::SafetyChecks;
+use super::iter::PieceTargets;
+use super::Game;
+use crate::board::{Board, Piece, PieceType, Player};
+use crate::game::{CastlingRights, GameState, ModifiableGame, MoveAction};
+
+use anyhow::{anyhow, Result};
+
+use std::collections::HashMap;
+use std::fmt;
+use std::path::PathBuf;
+
+fn piece_code(piece: &Piece) -> PieceCode {
+    let code = match piece.piece {
+        PieceType::Pawn => 1,
+        PieceType::Knight => 2,
+        PieceType::Bishop => 3,
+        PieceType::Rook => 4,
+        PieceType::Queen => 5,
+        PieceType::King => 6,
+    };
+
+    match piece.player {
+        Player::White => code,
+        Player::Black => code | BLACK_PIECE,
+    }
+}
+
+// Squares and pieces of the position, in ascending order of squares
+fn position_pieces(board: &impl Board) -> Vec<(usize, PieceCode)> {
+    Board::iter(board)
+        .filter_map(|position| {
+            let piece = board.at(&position)?;
+            Some((position.rank * 8 + position.file, piece_code(&piece)))
+        })
+        .collect()
+}
+
+fn is_pawn_move(board: &impl Board, mv: &MoveAction) -> bool {
+    board
+        .at(&mv.mv.source)
+        .is_some_and(|piece| piece.piece == PieceType::Pawn)
+}
+
+fn is_capture(board: &impl Board, mv: &MoveAction) -> bool {
+    // En passant is the only capture to an empty square
+    board.at(&mv.mv.target).is_some()
+        || (is_pawn_move(board, mv) && mv.mv.source.file != mv.mv.target.file)
+}
+
+// Captures and pawn moves reset the fifty-move rule
+fn is_zeroing(board: &impl Board, mv: &MoveAction) -> bool {
+    is_capture(board, mv) || is_pawn_move(board, mv)
+}
+
+fn is_check<B: Board + SafetyChecks + PieceTargets>(game: &GameState<B>) -> bool {
+    let board = game.board();
+    board.is_piece_unsafe(&board.find_king(&game.player()))
+}
+
+fn moved<B: Board>(game: &GameState<B>, mv: &MoveAction) -> Option<GameState<B>> {
+    let mut game = game.clone_unhashed();
+    ModifiableGame::do_move_no_checks(&mut game, mv).ok()?;
+    Some(game)
+}
+
+// Distance to zeroing of a position whose best move is a zeroing move
+fn dtz_before_zeroing(wdl: Wdl) -> i32 {
+    match wdl {
+        Wdl::Win => 1,
+        Wdl::CursedWin => 101,
+        Wdl::Draw => 0,
+        Wdl::BlessedLoss => -101,
+        Wdl::Loss => -1,
+    }
+}
+
+// WDL and DTZ tables of a material configuration
+struct TableFiles {
+    material: Material,
+    tables: [Option<Table>; 2],
+}
+
+impl TableFiles {
+    fn new(material: Material) -> Self {
+        TableFiles {
+            material,
+            tables: Default::default(),
+        }
+    }
+
+    fn table(&self, table_type: TableType) -> Option<&Table> {
+        self.tables[table_type as usize].as_ref()
+    }
+}
+
+/// Syzygy endgame tablebases, with the result of every position with few pieces under perfect
+/// play.
+///
+/// The WDL tables (`.rtbw` files) tell if a position is won, drawn or lost, and the DTZ tables
+/// (`.rtbz` files) the number of plies until the next capture or pawn move that keeps the result.
+pub struct Tablebase {
+    directories: Vec<PathBuf>,
+    tables: HashMap<Material, TableFiles>,
+    max_pieces: usize,
+}
+
+impl Tablebase {
+    /// Opens the tables in the directories of `paths`, separated like in the `PATH` environment
+    /// variable. Only the description of each table is read here, and the compressed results
+    /// are read from the files when the positions are probed.
+    pub fn open(paths: &str) -> Result<Self> {
+        let directories = std::env::split_paths(paths).collect::<Vec<PathBuf>>();
+        let mut tables: HashMap<Material, TableFiles> = HashMap::new();
+
+        for directory in &directories {
+            let entries = std::fs::read_dir(directory)
+                .map_err(|err| anyhow!("Cannot read {}: {}", directory.display(), err))?;
+
+            for entry in entries {
+                let path = entry?.path();
+                let table_type = match path.extension().and_then(|extension| extension.to_str()) {
+                    Some("rtbw") => TableType::Wdl,
+                    Some("rtbz") => TableType::Dtz,
+                    _ => continue,
+                };
+                let Some(material) = path
+                    .file_stem()
+                    .and_then(|name| name.to_str())
+                    .and_then(Material::try_from_name)
+                else {
+                    continue;
+                };
+
+                let table = Table::open(&path, &material, table_type)?;
+                tables
+                    .entry(material)
+                    .or_insert_with(|| TableFiles::new(material))
+                    .tables[table_type as usize] = Some(table);
+            }
+        }
+
+        let max_pieces = tables
+            .values()
+            .filter(|files| files.table(TableType::Wdl).is_some())
+            .map(|files| files.material.count())
+            .max()
+            .unwrap_or(0);
+
+        Ok(Tablebase {
+            directories,
+            tables,
+            max_pieces,
+        })
+    }
+
+    /// Number of material configurations with tables.
+    pub fn len(&self) -> usize {
+        self.tables.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tables.is_empty()
+    }
+
+    /// Maximum number of pieces of the positions in the tables, including the kings.
+    pub fn max_pieces(&self) -> usize {
+        self.max_pieces
+    }
+
+    // The tables do not have positions with castling rights
+    fn can_probe<B: Board>(&self, game: &GameState<B>) -> bool {
+        let can_castle = [Player::White, Player::Black]
+            .iter()
+            .any(|player| game.can_castle_kingside(*player) || game.can_castle_queenside(*player));
+
+        !can_castle && position_pieces(game.board()).len() <= self.max_pieces
+    }
+
+    // Value of the position in its table, or `None` if there is no table for its material.
+    // DTZ tables give `Some(None)` if they do not store the player to move.
+    fn probe_table<B: Board>(
+        &self,
+        game: &GameState<B>,
+        table_type: TableType,
+        wdl: Wdl,
+    ) -> Option<Option<i32>> {
+        let pieces = position_pieces(game.board());
+
+        // Only the kings
+        if pieces.len() == 2 {
+            return Some(Some(0));
+        }
+
+        let mut material = Material::default();
+        for (_, code) in &pieces {
+            material.add(*code);
+        }
+
+        // The tables have the stronger pieces as white
+        let (files, flip) = match self.tables.get(&material) {
+            Some(files) => (files, false),
+            None => (self.tables.get(&material.flipped())?, true),
+        };
+
+        Some(
+            files
+                .table(table_type)?
+                .probe(&pieces, game.player() == Player::Black, flip, wdl),
+        )
+    }
+
+    // WDL of the position, and whether the best move is a capture or a pawn move.
+    // The tables do not store the result of the positions where a capture is the best move, nor
+    // en passant captures, so the captures are searched too.
+    fn search<B: Board + SafetyChecks + PieceTargets>(
+        &self,
+        game: &GameState<B>,
+        search_pawn_moves: bool,
+    ) -> Option<(Wdl, bool)> {
+        let board = game.board();
+        let moves = game.get_all_possible_moves();
+        let mut searched_moves = 0;
+        let mut best_wdl = Wdl::Loss;
+
+        for mv in &moves {
+            let is_searched =
+                is_capture(board, mv) || (search_pawn_moves && is_pawn_move(board, mv));
+            if !is_searched {
+                continue;
+            }
+
+            searched_moves += 1;
+
+            let (wdl, _) = self.search(&moved(game, mv)?, false)?;
+            let wdl = -wdl;
+
+            if wdl > best_wdl {
+                best_wdl = wdl;
+
+                if wdl == Wdl::Win {
+                    return Some((wdl, true));
+                }
+            }
+        }
+
+        let all_moves_searched = searched_moves > 0 && searched_moves == moves.len();
+        let wdl = if all_moves_searched {
+            best_wdl
+        } else {
+            Wdl::from_value(self.probe_table(game, TableType::Wdl, Wdl::Draw)??)
+        };
+
+        if best_wdl >= wdl {
+            return Some((best_wdl, best_wdl > Wdl::Draw || all_moves_searched));
+        }
+
+        Some((wdl, false))
+    }
+
+    // Plies until the next capture or pawn move with perfect play, positive if the player to move
+    // wins and negative if it loses
+    fn dtz<B: Board + SafetyChecks + PieceTargets>(&self, game: &GameState<B>) -> Option<i32> {
+        let (wdl, is_zeroing_best) = self.search(game, true)?;
+
+        // Draws are not stored
+        if wdl == Wdl::Draw {
+            return Some(0);
+        }
+
+        if is_zeroing_best {
+            return Some(dtz_before_zeroing(wdl));
+        }
+
+        if let Some(dtz) = self.probe_table(game, TableType::Dtz, wdl)? {
+            let fifty_moves = match wdl {
+                Wdl::CursedWin | Wdl::BlessedLoss => 100,
+                _ => 0,
+            };
+            return Some((dtz + fifty_moves) * wdl.signum());
+        }
+
+        // The table only stores the other player to move, so search one ply
+        let mut min_dtz = i32::MAX;
+
+        for mv in game.get_all_possible_moves() {
+            let is_zeroing_move = is_zeroing(game.board(), &mv);
+            let next_game = moved(game, &mv)?;
+
+            // The distance of a zeroing move is counted before doing it
+            let mut dtz = if is_zeroing_move {
+                -dtz_before_zeroing(self.search(&next_game, false)?.0)
+            } else {
+                -self.dtz(&next_game)?
+            };
+
+            let is_check_mate =
+                dtz == 1 && is_check(&next_game) && next_game.get_all_possible_moves().is_empty();
+            if is_check_mate {
+                min_dtz = 1;
+            }
+
+            if !is_zeroing_move {
+                dtz += dtz.signum();
+            }
+
+            if dtz < min_dtz && dtz.signum() == wdl.signum() {
+                min_dtz = dtz;
+            }
+        }
+
+        // No legal moves: checkmate
+        Some(if min_dtz == i32::MAX { -1 } else { min_dtz })
+    }
+
+    /// Result of the position for the player to move, or `None` if it is not in the tables.
+    pub(super) fn probe_wdl<B: Board + SafetyChecks + PieceTargets>(
+        &self,
+        game: &GameState<B>,
+    ) -> Option<Wdl> {
+        if !self.can_probe(game) {
+            return None;
+        }
+
+        self.search(game, false).map(|(wdl, _)| wdl)
+    }
+
+    /// Distance to zeroing of each move of the current position, counted from the current
+    /// position, or `None` if it is not in the tables.
+    pub(super) fn root_dtz<B: Board + SafetyChecks + PieceTargets>(
+        &self,
+        game: &GameState<B>,
+        moves: &[MoveAction],
+    ) -> Option<Vec<i32>> {
+        if !self.can_probe(game) {
+            return None;
+        }
+
+        moves
+            .iter()
+            .map(|mv| {
+                let next_game = moved(game, mv)?;
+
+                let dtz = if is_zeroing(game.board(), mv) {
+                    dtz_before_zeroing(-self.search(&next_game, false)?.0)
+                } else {
+                    let dtz = -self.dtz(&next_game)?;
+                    dtz + dtz.signum()
+                };
+
+                // A checkmate is the fastest win
+                let is_check_mate = dtz == 2
+                    && is_check(&next_game)
+                    && next_game.get_all_possible_moves().is_empty();
+
+                Some(if is_check_mate { 1 } else { dtz })
+            })
+            .collect()
+    }
+}
+
+impl fmt::Debug for Tablebase {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Tablebase")
+            .field("directories", &self.directories)
+            .field("tables", &self.tables.len())
+            .field("max_pieces", &self.max_pieces)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+pub(super) mod tests {
+    use super::table::tests::write_table;
+    use super::*;
+    use crate::board::SimpleBoard;
+
+    use std::path::Path;
+
+    // Result of a position with perfect play, with the plies until checkmate
+    #[derive(Copy, Clone, Debug, PartialEq)]
+    enum Outcome {
+        Illegal,
+        Unknown,
+        Draw,
+        Win(i32),
+        Loss(i32),
+    }
+
+    const KING_STEPS: [(i32, i32); 8] = [
+        (-1, -1),
+        (-1, 0),
+        (-1, 1),
+        (0, -1),
+        (0, 1),
+        (1, -1),
+        (1, 0),
+        (1, 1),
+    ];
+
+    fn step(square: usize, (rank_inc, file_inc): (i32, i32)) -> Option<usize> {
+        let rank = (square / 8) as i32 + rank_inc;
+        let file = (square % 8) as i32 + file_inc;
+        ((0..8).contains(&rank) && (0..8).contains(&file)).then_some((rank * 8 + file) as usize)
+    }
+
+    fn is_adjacent(square: usize, other: usize) -> bool {
+        (square / 8).abs_diff(other / 8) <= 1 && (square % 8).abs_diff(other % 8) <= 1
+    }
+
+    // Squares reached by a sliding piece, up to the first blocker
+    fn slides(square: usize, directions: &[(i32, i32)], blocker: usize) -> Vec<usize> {
+        let mut targets = Vec::new();
+        for direction in directions {
+            let mut current = square;
+            while let Some(next) = step(current, *direction).filter(|next| *next != blocker) {
+                targets.push(next);
+                current = next;
+            }
+        }
+        targets
+    }
+
+    // Retrograde analysis of the positions of a white king and piece against a black king,
+    // indexed by the squares of the white king, the white piece and the black king, and the
+    // player to move
+    struct Solution {
+        outcomes: Vec<Outcome>,
+        directions: &'static [(i32, i32)],
+    }
+
+    fn index(white_king: usize, piece: usize, black_king: usize, black_to_move: bool) -> usize {
+        ((white_king * 64 + piece) * 64 + black_king) * 2 + usize::from(black_to_move)
+    }
+
+    impl Solution {
+        fn new(directions: &'static [(i32, i32)]) -> Solution {
+            let mut solution = Solution {
+                outcomes: vec![Outcome::Illegal; 64 * 64 * 64 * 2],
+                directions,
+            };
+
+            // Positions that reach each position in one move, and moves of black that do not
+            // lose yet
+            let mut predecessors = vec![Vec::new(); solution.outcomes.len()];
+            let mut remaining_moves = vec![0; solution.outcomes.len()];
+            let mut solved = Vec::new();
+
+            for white_king in 0..64 {
+                for piece in 0..64 {
+                    for black_king in 0..64 {
+                        let is_legal = white_king != piece
+                            && piece != black_king
+                            && !is_adjacent(white_king, black_king);
+                        if !is_legal {
+                            continue;
+                        }
+
+                        let in_check = solution.in_check(white_king, piece, black_king);
+                        let white_position = index(white_king, piece, black_king, false);
+                        if !in_check {
+                            solution.outcomes[white_position] = Outcome::Unknown;
+                            for next in solution.white_moves(white_king, piece, black_king) {
+                                predecessors[next].push(white_position);
+                            }
+                        }
+
+                        let black_position = index(white_king, piece, black_king, true);
+                        let black_moves = solution.black_moves(white_king, piece, black_king);
+                        for next in black_moves.iter().flatten() {
+                            predecessors[*next].push(black_position);
+                        }
+                        remaining_moves[black_position] = black_moves.len();
+
+                        solution.outcomes[black_position] =
+                            match (!black_moves.is_empty(), in_check) {
+                                (true, _) => Outcome::Unknown,
+                                (false, true) => Outcome::Loss(0),
+                                (false, false) => Outcome::Draw,
+                            };
+                        if in_check && black_moves.is_empty() {
+                            solved.push(black_position);
+                        }
+                    }
+                }
+            }
+
+            // Wins in an odd number of plies for white, when any move reaches a loss, and losses
+            // in an even number for black, when all the moves reach a win
+            let mut plies = 0;
+            while !solved.is_empty() {
+                plies += 1;
+                let mut next_solved = Vec::new();
+
+                for position in solved {
+                    for previous in &predecessors[position] {
+                        if solution.outcomes[*previous] != Outcome::Unknown {
+                            continue;
+                        }
+
+                        if previous % 2 == 0 {
+                            solution.outcomes[*previous] = Outcome::Win(plies);
+                        } else {
+                            remaining_moves[*previous] -= 1;
+                            if remaining_moves[*previous] > 0 {
+                                continue;
+                            }
+                            solution.outcomes[*previous] = Outcome::Loss(plies);
+                        }
+                        next_solved.push(*previous);
+                    }
+                }
+
+                solved = next_solved;
+            }
+
+            for outcome in solution.outcomes.iter_mut() {
+                if *outcome == Outcome::Unknown {
+                    *outcome = Outcome::Draw;
+                }
+            }
+
+            solution
+        }
+
+        fn in_check(&self, white_king: usize, piece: usize, black_king: usize) -> bool {
+            self.directions.iter().any(|direction| {
+                let mut current = piece;
+                while let Some(next) = step(current, *direction).filter(|next| *next != white_king)
+                {
+                    if next == black_king {
+                        return true;
+                    }
+                    current = next;
+                }
+                false
+            })
+        }
+
+        fn outcome(
+            &self,
+            white_king: usize,
+            piece: usize,
+            black_king: usize,
+            black_to_move: bool,
+        ) -> Outcome {
+            self.outcomes[index(white_king, piece, black_king, black_to_move)]
+        }
+
+        // Positions after the legal moves of black, where capturing the piece is `None`
+        fn black_moves(
+            &self,
+            white_king: usize,
+            piece: usize,
+            black_king: usize,
+        ) -> Vec<Option<usize>> {
+            KING_STEPS
+                .iter()
+                .filter_map(|direction| step(black_king, *direction))
+                .filter(|target| !is_adjacent(*target, white_king))
+                .filter_map(|target| {
+                    if target == piece {
+                        Some(None)
+                    } else if self.in_check(white_king, piece, target) {
+                        None
+                    } else {
+                        Some(Some(index(white_king, piece, target, false)))
+                    }
+                })
+                .collect()
+        }
+
+        // Positions after the legal moves of white
+        fn white_moves(&self, white_king: usize, piece: usize, black_king: usize) -> Vec<usize> {
+            let king_moves = KING_STEPS
+                .iter()
+                .filter_map(|direction| step(white_king, *direction))
+                .filter(|target| *target != piece && !is_adjacent(*target, black_king))
+                .map(|target| index(target, piece, black_king, true));
+
+            let piece_moves = slides(piece, self.directions, white_king)
+                .into_iter()
+                .filter(|target| *target != black_king)
+                .map(|target| index(white_king, target, black_king, true))
+                .collect::<Vec<usize>>();
+
+            king_moves.chain(piece_moves).collect()
+        }
+    }
+
+    fn game(squares: [(usize, char); 3], black_to_move: bool) -> GameState<SimpleBoard> {
+        let mut board = ['.'; 64];
+        for (square, piece) in squares {
+            board[square] = piece;
+        }
+
+        let ranks = (0..8)
+            .rev()
+            .map(|rank| {
+                let mut fen_rank = String::new();
+                let mut empty = 0;
+                for piece in &board[rank * 8..rank * 8 + 8] {
+                    if *piece == '.' {
+                        empty += 1;
+                        continue;
+                    }
+                    if empty > 0 {
+                        fen_rank.push_str(&empty.to_string());
+                        empty = 0;
+                    }
+                    fen_rank.push(*piece);
+                }
+                if empty > 0 {
+                    fen_rank.push_str(&empty.to_string());
+                }
+                fen_rank
+            })
+            .collect::<Vec<String>>()
+            .join("/");
+
+        let player = if black_to_move { "b" } else { "w" };
+        GameState::try_from_fen(&[&ranks, player, "-", "-", "0", "1"]).unwrap()
+    }
+
+    // Distance to zeroing of the position, which is the distance to the checkmate without pawns
+    // nor captures that keep the result
+    fn expected_dtz(outcome: Outcome) -> i32 {
+        match outcome {
+            Outcome::Win(plies) => plies,
+            // The player to move is checkmated
+            Outcome::Loss(0) => -1,
+            Outcome::Loss(plies) => -plies,
+            _ => 0,
+        }
+    }
+
+    // The DTZ tables can store the distance in moves instead of plies, which can be one ply off
+    fn is_dtz_close(dtz: i32, expected: i32) -> bool {
+        dtz.signum() == expected.signum() && dtz.abs_diff(expected) <= 1
+    }
+
+    const ROOK: [(i32, i32); 4] = [(-1, 0), (0, -1), (0, 1), (1, 0)];
+
+    fn solutions() -> [(char, Solution); 2] {
+        [
+            ('Q', Solution::new(&KING_STEPS)),
+            ('R', Solution::new(&ROOK)),
+        ]
+    }
+
+    fn write_solutions(directory: &Path, solutions: &[(char, Solution)]) {
+        for (letter, solution) in solutions {
+            let code = match letter {
+                'Q' => 5,
+                _ => 4,
+            };
+
+            let mut wdl_positions = Vec::new();
+            let mut dtz_positions = Vec::new();
+            for (position, outcome) in solution.outcomes.iter().enumerate() {
+                if *outcome == Outcome::Illegal {
+                    continue;
+                }
+
+                let (white_king, piece, black_king) = (
+                    position / 2 / 64 / 64,
+                    position / 2 / 64 % 64,
+                    position / 2 % 64,
+                );
+                let black_to_move = position % 2 == 1;
+                let mut pieces = vec![
+                    (white_king, 6),
+                    (piece, code),
+                    (black_king, 6 | BLACK_PIECE),
+                ];
+                pieces.sort();
+
+                let wdl = expected_dtz(*outcome).signum() * 2;
+                wdl_positions.push((pieces.clone(), black_to_move, wdl));
+                dtz_positions.push((pieces, black_to_move, expected_dtz(*outcome)));
+            }
+
+            let name = format!("K{}vK", letter);
+            for (extension, table_type, positions) in [
+                ("rtbw", TableType::Wdl, wdl_positions),
+                ("rtbz", TableType::Dtz, dtz_positions),
+            ] {
+                let path = directory.join(format!("{}.{}", name, extension));
+                write_table(&path, &name, table_type, &positions).unwrap();
+            }
+        }
+    }
+
+    /// Writes the tables KQvK and KRvK to the directory, from their retrograde analysis.
+    pub(in crate::eval) fn write_tables(directory: &Path) {
+        write_solutions(directory, &solutions());
+    }
+
+    // The Syzygy tables are not available to the tests, so tables with the same format are
+    // written from the retrograde analysis
+    #[test]
+    fn three_pieces() {
+        let directory = std::env::temp_dir().join(format!("chusst-syzygy-{}", std::process::id()));
+        std::fs::create_dir_all(&directory).unwrap();
+
+        let solutions = solutions();
+        write_solutions(&directory, &solutions);
+        let tablebase = Tablebase::open(directory.to_str().unwrap()).unwrap();
+        check_three_pieces(&tablebase, &solutions, 101);
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    #[ignore = "needs the Syzygy tables KQvK and KRvK, in the directory of SYZYGY_PATH"]
+    fn syzygy_three_pieces() {
+        let path = std::env::var("SYZYGY_PATH").expect("SYZYGY_PATH is not set");
+        let tablebase = Tablebase::open(&path).unwrap();
+
+        check_three_pieces(&tablebase, &solutions(), 1);
+    }
+
+    // Checks one of every `stride` positions against the retrograde analysis
+    fn check_three_pieces(tablebase: &Tablebase, solutions: &[(char, Solution)], stride: usize) {
+        assert!(tablebase.max_pieces() >= 3);
+
+        for ((letter, solution), longest_mate) in std::iter::zip(solutions, [19, 31]) {
+            let letter = *letter;
+
+            // Longest mates of 10 and 16 moves
+            let longest = solution
+                .outcomes
+                .iter()
+                .filter_map(|outcome| match outcome {
+                    Outcome::Win(plies) => Some(*plies),
+                    _ => None,
+                });
+            assert_eq!(longest.max(), Some(longest_mate));
+
+            for (position, outcome) in solution.outcomes.iter().enumerate().step_by(stride) {
+                if *outcome == Outcome::Illegal {
+                    continue;
+                }
+
+                let (white_king, piece, black_king) = (
+                    position / 2 / 64 / 64,
+                    position / 2 / 64 % 64,
+                    position / 2 % 64,
+                );
+                let black_to_move = position % 2 == 1;
+                let game = game(
+                    [(white_king, 'K'), (piece, letter), (black_king, 'k')],
+                    black_to_move,
+                );
+                let board = game.board();
+
+                let wdl = match outcome {
+                    Outcome::Win(_) => Wdl::Win,
+                    Outcome::Loss(_) => Wdl::Loss,
+                    _ => Wdl::Draw,
+                };
+                assert_eq!(tablebase.probe_wdl(&game), Some(wdl), "WDL of\n{}", board);
+                assert_eq!(
+                    tablebase.search(&game, true).map(|(wdl, _)| wdl),
+                    Some(wdl),
+                    "search of\n{}",
+                    board
+                );
+
+                // The DTZ of the rest of positions is checked below, through their moves
+                if position % 7 != 0 {
+                    continue;
+                }
+
+                let expected = expected_dtz(*outcome);
+                let dtz = tablebase.dtz(&game).unwrap();
+                assert!(
+                    is_dtz_close(dtz, expected),
+                    "DTZ {} instead of {} of\n{}",
+                    dtz,
+                    expected,
+                    board
+                );
+
+                let moves = game.get_all_possible_moves();
+                let root_dtz = tablebase.root_dtz(&game, &moves).unwrap();
+                for (mv, dtz) in std::iter::zip(&moves, root_dtz) {
+                    let source = mv.mv.source.rank * 8 + mv.mv.source.file;
+                    let target = mv.mv.target.rank * 8 + mv.mv.target.file;
+                    let next_outcome = match (source, target) {
+                        // Capture of the piece
+                        (_, target) if target == piece => Outcome::Draw,
+                        (source, target) if source == white_king => {
+                            solution.outcome(target, piece, black_king, true)
+                        }
+                        (source, target) if source == piece => {
+                            solution.outcome(white_king, target, black_king, true)
+                        }
+                        (_, target) => solution.outcome(white_king, piece, target, false),
+                    };
+
+                    let expected = match next_outcome {
+                        // A checkmate is the fastest win
+                        Outcome::Loss(0) => 1,
+                        outcome => {
+                            let dtz = -expected_dtz(outcome);
+                            dtz + dtz.signum()
+                        }
+                    };
+                    assert!(
+                        is_dtz_close(dtz, expected),
+                        "DTZ {} instead of {} of {} in\n{}",
+                        dtz,
+                        expected,
+                        mv.mv,
+                        board
+                    );
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,1528 @@
+// Reader of the Syzygy tablebase files, which store the result of every position of a material
+// configuration compressed with Huffman codes of recursively paired symbols.
+// The format is described in the probing code of Ronald de Man, the author of the tables.
+
+use anyhow::{anyhow, bail, Result};
+use lazy_static::lazy_static;
+
+use std::fs::File;
+use std::path::Path;
+
+// Maximum number of pieces of a table, including the kings
+pub(super) const MAX_PIECES: usize = 7;
+
+const WDL_MAGIC: [u8; 4] = [0x71, 0xe8, 0x23, 0x5d];
+const DTZ_MAGIC: [u8; 4] = [0xd7, 0x66, 0x0c, 0xa5];
+
+// Flags of the file header
+const HEADER_SPLIT: u8 = 1;
+const HEADER_HAS_PAWNS: u8 = 2;
+
+// Flags of each sub-table
+const FLAG_STM: u8 = 1;
+const FLAG_MAPPED: u8 = 2;
+const FLAG_WIN_PLIES: u8 = 4;
+const FLAG_LOSS_PLIES: u8 = 8;
+const FLAG_WIDE: u8 = 16;
+const FLAG_SINGLE_VALUE: u8 = 128;
+
+// Value of a leaf of the symbol tree
+const LEAF_SYMBOL: usize = 0xfff;
+
+// Bytes first read from the files, which are enough for the description of most tables
+const INITIAL_READ: u64 = 4096;
+
+// Pieces in the tables are coded as the piece type (pawn, knight, bishop, rook, queen, king),
+// starting from 1, plus 8 for black pieces
+pub(super) type PieceCode = u8;
+
+pub(super) const BLACK_PIECE: PieceCode = 8;
+
+// Encoding of the squares, from A1 = 0 to H8 = 63
+type Square = usize;
+
+fn rank(square: Square) -> usize {
+    square / 8
+}
+
+fn file(square: Square) -> usize {
+    square % 8
+}
+
+// Distance of the square to the A1-H8 diagonal, positive above it
+fn off_diagonal(square: Square) -> i32 {
+    rank(square) as i32 - file(square) as i32
+}
+
+fn flip_rank(square: Square) -> Square {
+    square ^ 56
+}
+
+fn flip_file(square: Square) -> Square {
+    square ^ 7
+}
+
+fn flip_diagonal(square: Square) -> Square {
+    ((square >> 3) | (square << 3)) & 63
+}
+
+#[derive(Copy, Clone, PartialEq)]
+pub(super) enum TableType {
+    Wdl,
+    Dtz,
+}
+
+/// Result of a position with perfect play, from the point of view of the player to move.
+///
+/// A cursed win is a win that takes longer than the fifty-move rule, and a blessed loss is a
+/// loss that can be held to a draw by the fifty-move rule.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Wdl {
+    Loss = -2,
+    BlessedLoss = -1,
+    Draw = 0,
+    CursedWin = 1,
+    Win = 2,
+}
+
+impl Wdl {
+    pub(super) fn from_value(value: i32) -> Wdl {
+        match value {
+            ..=-2 => Wdl::Loss,
+            -1 => Wdl::BlessedLoss,
+            0 => Wdl::Draw,
+            1 => Wdl::CursedWin,
+            _ => Wdl::Win,
+        }
+    }
+
+    pub(super) fn signum(&self) -> i32 {
+        (*self as i32).signum()
+    }
+}
+
+impl std::ops::Neg for Wdl {
+    type Output = Self;
+
+    fn neg(self) -> Self::Output {
+        Wdl::from_value(-(self as i32))
+    }
+}
+
+/// Number of pieces of each type and player, which identifies a table.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub(super) struct Material([[u8; 6]; 2]);
+
+// Pieces in the order of the names of the tables
+const NAME_PIECES: [(char, PieceCode); 6] =
+    [('K', 6), ('Q', 5), ('R', 4), ('B', 3), ('N', 2), ('P', 1)];
+
+impl Material {
+    pub fn add(&mut self, code: PieceCode) {
+        let player = usize::from(code & BLACK_PIECE != 0);
+        self.0[player][usize::from(code & !BLACK_PIECE) - 1] += 1;
+    }
+
+    // Table name such as KRvKN, with the pieces of white first
+    pub fn try_from_name(name: &str) -> Option<Material> {
+        let (white, black) = name.split_once('v')?;
+        let mut material = Material::default();
+
+        for (player, pieces) in [(0, white), (BLACK_PIECE, black)] {
+            for piece in pieces.chars() {
+                let (_, code) = NAME_PIECES.iter().find(|(letter, _)| *letter == piece)?;
+                material.add(code | player);
+            }
+        }
+
+        let has_kings = material.0.iter().all(|pieces| pieces[5] == 1);
+        (has_kings && material.count() <= MAX_PIECES).then_some(material)
+    }
+
+    pub fn flipped(&self) -> Material {
+        Material([self.0[1], self.0[0]])
+    }
+
+    pub fn count(&self) -> usize {
+        self.0
+            .iter()
+            .flatten()
+            .map(|count| usize::from(*count))
+            .sum()
+    }
+
+    fn pawns(&self, player: usize) -> usize {
+        usize::from(self.0[player][0])
+    }
+}
+
+struct IndexTables {
+    // Ways to choose k elements out of n, as binomial[k][n]
+    binomial: [[u64; 64]; MAX_PIECES],
+    // Squares below the A1-H8 diagonal, from 0 to 27
+    map_b1h1h7: [u64; 64],
+    // Squares of the A1-D1-D4 triangle, from 0 to 9, with the diagonal last
+    map_a1d1d4: [u64; 64],
+    // Legal positions of two kings, from 0 to 461, with the first one in the A1-D1-D4 triangle
+    map_kk: [[u64; 64]; 10],
+    // Squares from A2 to H7, the leading pawn being the one with the highest value
+    map_pawns: [usize; 64],
+    // Index of the leading pawns, by number of leading pawns and square of the first one
+    lead_pawn_index: [[u64; 64]; MAX_PIECES],
+    // Number of positions of the leading pawns, by number of leading pawns and file
+    lead_pawns_size: [[u64; 4]; MAX_PIECES],
+}
+
+impl IndexTables {
+    fn new() -> IndexTables {
+        let mut tables = IndexTables {
+            binomial: [[0; 64]; MAX_PIECES],
+            map_b1h1h7: [0; 64],
+            map_a1d1d4: [0; 64],
+            map_kk: [[0; 64]; 10],
+            map_pawns: [0; 64],
+            lead_pawn_index: [[0; 64]; MAX_PIECES],
+            lead_pawns_size: [[0; 4]; MAX_PIECES],
+        };
+
+        let mut code = 0;
+        for square in 0..64 {
+            if off_diagonal(square) < 0 {
+                tables.map_b1h1h7[square] = code;
+                code += 1;
+            }
+        }
+
+        let mut diagonal = Vec::new();
+        code = 0;
+        for square in (0..64).filter(|square| rank(*square) <= 3 && file(*square) <= 3) {
+            if off_diagonal(square) < 0 {
+                tables.map_a1d1d4[square] = code;
+                code += 1;
+            } else if off_diagonal(square) == 0 {
+                diagonal.push(square);
+            }
+        }
+        for square in diagonal {
+            tables.map_a1d1d4[square] = code;
+            code += 1;
+        }
+
+        let is_king_adjacent = |square: Square, other: Square| {
+            rank(square).abs_diff(rank(other)) <= 1 && file(square).abs_diff(file(other)) <= 1
+        };
+        let mut both_on_diagonal = Vec::new();
+        code = 0;
+        for index in 0..10 {
+            for first in 0..=27 {
+                let is_in_triangle = rank(first) <= 3 && file(first) <= 3;
+                // B1 is mapped to 0, like the squares outside of the triangle
+                if !is_in_triangle
+                    || tables.map_a1d1d4[first] != index
+                    || (index == 0 && first != 1)
+                {
+                    continue;
+                }
+
+                for second in 0..64 {
+                    if is_king_adjacent(first, second) {
+                        continue;
+                    }
+
+                    if off_diagonal(first) == 0 && off_diagonal(second) > 0 {
+                        // Mirrored by the diagonal
+                        continue;
+                    } else if off_diagonal(first) == 0 && off_diagonal(second) == 0 {
+                        both_on_diagonal.push((index, second));
+                    } else {
+                        tables.map_kk[index as usize][second] = code;
+                        code += 1;
+                    }
+                }
+            }
+        }
+        for (index, second) in both_on_diagonal {
+            tables.map_kk[index as usize][second] = code;
+            code += 1;
+        }
+
+        tables.binomial[0][0] = 1;
+        for n in 1..64 {
+            for k in 0..MAX_PIECES.min(n + 1) {
+                tables.binomial[k][n] = if k > 0 {
+                    tables.binomial[k - 1][n - 1]
+                } else {
+                    0
+                } + if k < n { tables.binomial[k][n - 1] } else { 0 };
+            }
+        }
+
+        let mut available_squares = 47;
+        for lead_pawns in 1..MAX_PIECES - 1 {
+            for file in 0..4 {
+                let mut index = 0;
+                for rank in 1..7 {
+                    let square = rank * 8 + file;
+                    if lead_pawns == 1 {
+                        tables.map_pawns[square] = available_squares;
+                        tables.map_pawns[flip_file(square)] = available_squares - 1;
+                        available_squares = available_squares.saturating_sub(2);
+                    }
+                    tables.lead_pawn_index[lead_pawns][square] = index;
+                    index += tables.binomial[lead_pawns - 1][tables.map_pawns[square]];
+                }
+                tables.lead_pawns_size[lead_pawns][file] = index;
+            }
+        }
+
+        tables
+    }
+}
+
+lazy_static! {
+    static ref INDEX_TABLES: IndexTables = IndexTables::new();
+}
+
+// Decoding data of a sub-table, for one side to move and one file of the leading pawn.
+// Offsets are from the start of the file.
+#[derive(Clone, Default)]
+struct PairsData {
+    flags: u8,
+    // Minimum length of the symbols in bits, or the value of all the positions
+    min_sym_len: u8,
+    block_size: usize,
+    // Distance between the entries of the sparse index
+    span: u64,
+    num_blocks: usize,
+    block_lengths_size: usize,
+    sparse_index_size: usize,
+    lowest_sym: usize,
+    btree: usize,
+    block_lengths: usize,
+    sparse_index: usize,
+    data: usize,
+    // Lowest symbol of each length, left aligned to 64 bits
+    base64: Vec<u64>,
+    // Number of values minus one represented by each symbol
+    symlen: Vec<usize>,
+    // Order of the pieces in the encoding
+    pieces: [PieceCode; MAX_PIECES],
+    // Pieces encoded together, and the multiplier of their index
+    group_len: [usize; MAX_PIECES + 1],
+    group_index: [u64; MAX_PIECES + 1],
+    // Start of the value maps of the DTZ tables: win, loss, cursed win and blessed loss
+    map_index: [usize; 4],
+}
+
+impl PairsData {
+    fn size(&self) -> u64 {
+        let groups = self.group_len.iter().position(|len| *len == 0).unwrap();
+        self.group_index[groups]
+    }
+}
+
+// Reads the bytes of the file at this offset, from any thread
+#[cfg(unix)]
+fn read_at(file: &File, buffer: &mut [u8], offset: u64) -> std::io::Result<()> {
+    std::os::unix::fs::FileExt::read_exact_at(file, buffer, offset)
+}
+
+#[cfg(windows)]
+fn read_at(file: &File, buffer: &mut [u8], offset: u64) -> std::io::Result<()> {
+    use std::os::windows::fs::FileExt;
+
+    let mut read = 0;
+    while read < buffer.len() {
+        match file.seek_read(&mut buffer[read..], offset + read as u64)? {
+            0 => return Err(std::io::ErrorKind::UnexpectedEof.into()),
+            bytes => read += bytes,
+        }
+    }
+    Ok(())
+}
+
+pub(super) struct Table {
+    file: File,
+    size: u64,
+    // Start of the file until the compressed data, which is read from the file when probed
+    data: Vec<u8>,
+    table_type: TableType,
+    // The material is the same for both players
+    symmetric: bool,
+    has_pawns: bool,
+    has_unique_pieces: bool,
+    piece_count: usize,
+    // Pawns of the leading player and of the other player
+    pawn_count: [usize; 2],
+    // Sub-tables by side to move and file of the leading pawn
+    pairs: Vec<Vec<PairsData>>,
+}
+
+impl Table {
+    pub fn open(path: &Path, material: &Material, table_type: TableType) -> Result<Table> {
+        let read_error = |err| anyhow!("Cannot read {}: {}", path.display(), err);
+        let file = File::open(path).map_err(read_error)?;
+        let size = file.metadata().map_err(read_error)?.len();
+
+        if size % 64 != 16 {
+            bail!("Corrupted tablebase file {}", path.display());
+        }
+
+        let mut magic = [0; 4];
+        read_at(&file, &mut magic, 0).map_err(read_error)?;
+        let expected_magic = match table_type {
+            TableType::Wdl => WDL_MAGIC,
+            TableType::Dtz => DTZ_MAGIC,
+        };
+        if magic != expected_magic {
+            bail!("Invalid tablebase file {}", path.display());
+        }
+
+        // With pawns on both sides, the player with less pawns leads
+        let white_leads = material.pawns(1) == 0
+            || (material.pawns(0) > 0 && material.pawns(1) >= material.pawns(0));
+        let pawn_count = if white_leads {
+            [material.pawns(0), material.pawns(1)]
+        } else {
+            [material.pawns(1), material.pawns(0)]
+        };
+
+        let mut table = Table {
+            file,
+            size,
+            data: Vec::new(),
+            table_type,
+            symmetric: material.flipped() == *material,
+            has_pawns: pawn_count[0] > 0,
+            has_unique_pieces: material.0.iter().any(|pieces| pieces[..5].contains(&1)),
+            piece_count: material.count(),
+            pawn_count,
+            pairs: Vec::new(),
+        };
+
+        // The description of the table is read until it is complete
+        let mut read_size = INITIAL_READ.min(size);
+        loop {
+            table.data = vec![0; read_size as usize];
+            read_at(&table.file, &mut table.data, 0).map_err(read_error)?;
+
+            if table.setup().is_some() {
+                return Ok(table);
+            }
+
+            if read_size == size {
+                bail!("Corrupted tablebase file {}", path.display());
+            }
+            read_size = (read_size * 2).min(size);
+        }
+    }
+
+    fn byte(&self, offset: usize) -> Option<usize> {
+        self.data.get(offset).copied().map(usize::from)
+    }
+
+    fn read_le<const N: usize>(&self, offset: usize) -> Option<[u8; N]> {
+        self.data.get(offset..offset + N)?.try_into().ok()
+    }
+
+    fn u16_le(&self, offset: usize) -> Option<usize> {
+        Some(usize::from(u16::from_le_bytes(self.read_le(offset)?)))
+    }
+
+    fn u32_le(&self, offset: usize) -> Option<usize> {
+        Some(u32::from_le_bytes(self.read_le(offset)?) as usize)
+    }
+
+    // Block of compressed data, or `None` if it cannot be read
+    fn read_block(&self, sub_table: &PairsData, block: usize) -> Option<Vec<u8>> {
+        let mut bytes = vec![0; sub_table.block_size];
+        let offset = sub_table.data + block * sub_table.block_size;
+        read_at(&self.file, &mut bytes, offset as u64).ok()?;
+        Some(bytes)
+    }
+
+    fn sides(&self) -> usize {
+        match self.table_type {
+            TableType::Wdl if !self.symmetric => 2,
+            _ => 1,
+        }
+    }
+
+    fn files(&self) -> usize {
+        if self.has_pawns {
+            4
+        } else {
+            1
+        }
+    }
+
+    fn sub_table(&self, side: usize, file: usize) -> &PairsData {
+        &self.pairs[side % self.pairs.len()][file]
+    }
+
+    fn setup(&mut self) -> Option<()> {
+        let header = self.byte(4)?;
+        let mut offset = 5;
+
+        if (header & usize::from(HEADER_HAS_PAWNS) != 0) != self.has_pawns
+            || (header & usize::from(HEADER_SPLIT) != 0) == self.symmetric
+        {
+            return None;
+        }
+
+        let sides = self.sides();
+        let files = self.files();
+        let both_pawns = self.has_pawns && self.pawn_count[1] > 0;
+
+        let mut pairs = vec![vec![PairsData::default(); files]; sides];
+
+        for file in 0..files {
+            let first_order = self.byte(offset)?;
+            let second_order = if both_pawns {
+                self.byte(offset + 1)?
+            } else {
+                0xff
+            };
+            let order = [
+                [first_order & 0xf, second_order & 0xf],
+                [first_order >> 4, second_order >> 4],
+            ];
+            offset += 1 + usize::from(both_pawns);
+
+            for piece in 0..self.piece_count {
+                let codes = self.byte(offset)? as u8;
+                for (side, side_pairs) in pairs.iter_mut().enumerate() {
+                    side_pairs[file].pieces[piece] =
+                        if side == 0 { codes & 0xf } else { codes >> 4 };
+                }
+                offset += 1;
+            }
+
+            for (side, side_pairs) in pairs.iter_mut().enumerate() {
+                self.set_groups(&mut side_pairs[file], order[side], file);
+            }
+        }
+
+        offset += offset & 1;
+
+        for file in 0..files {
+            for side_pairs in pairs.iter_mut() {
+                offset = self.set_sizes(&mut side_pairs[file], offset)?;
+            }
+        }
+
+        if self.table_type == TableType::Dtz {
+            offset = self.set_dtz_map(&mut pairs[0], offset)?;
+        }
+
+        for file in 0..files {
+            for side_pairs in pairs.iter_mut() {
+                let sub_table = &mut side_pairs[file];
+                sub_table.sparse_index = offset;
+                offset += sub_table.sparse_index_size * 6;
+            }
+        }
+
+        for file in 0..files {
+            for side_pairs in pairs.iter_mut() {
+                let sub_table = &mut side_pairs[file];
+                sub_table.block_lengths = offset;
+                offset += sub_table.block_lengths_size * 2;
+            }
+        }
+
+        let description_size = offset;
+        for file in 0..files {
+            for side_pairs in pairs.iter_mut() {
+                let sub_table = &mut side_pairs[file];
+                offset = (offset + 0x3f) & !0x3f;
+                sub_table.data = offset;
+                offset += sub_table.num_blocks * sub_table.block_size;
+            }
+        }
+
+        if description_size > self.data.len() || offset as u64 > self.size {
+            return None;
+        }
+
+        self.data.truncate(description_size);
+        self.pairs = pairs;
+
+        Some(())
+    }
+
+    // The pieces are encoded in groups, the first one with the leading pieces or pawns, and
+    // the index of the position is the combination of the index of each group:
+    //   g1 * N(g2) * N(g3) + g2 * N(g3) + g3
+    // The order of the groups is given by the table.
+    fn set_groups(&self, sub_table: &mut PairsData, order: [usize; 2], file: usize) {
+        let tables = &*INDEX_TABLES;
+        let mut first_len: i32 = if self.has_pawns {
+            0
+        } else if self.has_unique_pieces {
+            3
+        } else {
+            2
+        };
+        let mut groups = 0;
+        sub_table.group_len[0] = 1;
+
+        for piece in 1..self.piece_count {
+            first_len -= 1;
+            if first_len > 0 || sub_table.pieces[piece] == sub_table.pieces[piece - 1] {
+                sub_table.group_len[groups] += 1;
+            } else {
+                groups += 1;
+                sub_table.group_len[groups] = 1;
+            }
+        }
+        groups += 1;
+        sub_table.group_len[groups] = 0;
+
+        let both_pawns = self.has_pawns && self.pawn_count[1] > 0;
+        let mut next = if both_pawns { 2 } else { 1 };
+        let mut free_squares = 64
+            - sub_table.group_len[0]
+            - if both_pawns {
+                sub_table.group_len[1]
+            } else {
+                0
+            };
+        let mut index: u64 = 1;
+
+        let mut k = 0;
+        while next < groups || k == order[0] || k == order[1] {
+            if k == order[0] {
+                // Leading pawns or pieces
+                sub_table.group_index[0] = index;
+                index *= if self.has_pawns {
+                    tables.lead_pawns_size[sub_table.group_len[0]][file]
+                } else if self.has_unique_pieces {
+                    31332
+                } else {
+                    462
+                };
+            } else if k == order[1] {
+                // Rest of the pawns
+                sub_table.group_index[1] = index;
+                index *= tables.binomial[sub_table.group_len[1]][48 - sub_table.group_len[0]];
+            } else {
+                // Rest of the pieces
+                sub_table.group_index[next] = index;
+                index *= tables.binomial[sub_table.group_len[next]][free_squares];
+                free_squares -= sub_table.group_len[next];
+                next += 1;
+            }
+            k += 1;
+        }
+
+        sub_table.group_index[groups] = index;
+    }
+
+    fn set_sizes(&self, sub_table: &mut PairsData, offset: usize) -> Option<usize> {
+        let mut offset = offset;
+
+        sub_table.flags = self.byte(offset)? as u8;
+        offset += 1;
+
+        if sub_table.flags & FLAG_SINGLE_VALUE != 0 {
+            sub_table.min_sym_len = self.byte(offset)? as u8;
+            return Some(offset + 1);
+        }
+
+        sub_table.block_size = 1usize.checked_shl(self.byte(offset)? as u32)?;
+        sub_table.span = 1u64.checked_shl(self.byte(offset + 1)? as u32)?;
+        sub_table.sparse_index_size = sub_table.size().div_ceil(sub_table.span) as usize;
+        let padding = self.byte(offset + 2)?;
+        sub_table.num_blocks = self.u32_le(offset + 3)?;
+        // Padded so that the sparse index does not point out of range
+        sub_table.block_lengths_size = sub_table.num_blocks + padding;
+        let max_sym_len = self.byte(offset + 7)?;
+        sub_table.min_sym_len = self.byte(offset + 8)? as u8;
+        offset += 9;
+
+        let min_sym_len = usize::from(sub_table.min_sym_len);
+        let sym_lengths = max_sym_len.checked_sub(min_sym_len)? + 1;
+        sub_table.lowest_sym = offset;
+
+        // Canonical Huffman code: longer symbols have lower values, so the lowest symbol of
+        // each length, left aligned, is lower than the ones of the shorter lengths
+        let mut base64 = vec![0u64; sym_lengths];
+        for length in (0..sym_lengths - 1).rev() {
+            let lowest = self.u16_le(offset + length * 2)? as u64;
+            let next_lowest = self.u16_le(offset + (length + 1) * 2)? as u64;
+            base64[length] = base64[length + 1]
+                .wrapping_add(lowest)
+                .wrapping_sub(next_lowest)
+                / 2;
+        }
+        for (length, base) in base64.iter_mut().enumerate() {
+            *base = base
+                .checked_shl((64 - length - min_sym_len) as u32)
+                .unwrap_or(0);
+        }
+        sub_table.base64 = base64;
+        offset += sym_lengths * 2;
+
+        let symbols = self.u16_le(offset)?;
+        offset += 2;
+        sub_table.btree = offset;
+        if offset + symbols * 3 > self.data.len() {
+            return None;
+        }
+
+        // Each symbol is a pair of symbols, expanded until the leaves of the tree
+        sub_table.symlen = vec![0; symbols];
+        let mut visited = vec![false; symbols];
+        for symbol in 0..symbols {
+            if !visited[symbol] {
+                sub_table.symlen[symbol] = self.set_symlen(sub_table, symbol, &mut visited)?;
+            }
+        }
+
+        Some(offset + symbols * 3 + (symbols & 1))
+    }
+
+    fn set_symlen(
+        &self,
+        sub_table: &mut PairsData,
+        symbol: usize,
+        visited: &mut [bool],
+    ) -> Option<usize> {
+        visited[symbol] = true;
+
+        let (left, right) = self.symbol_pair(sub_table, symbol);
+        if right == LEAF_SYMBOL {
+            return Some(0);
+        }
+
+        for child in [left, right] {
+            if !*visited.get(child)? {
+                sub_table.symlen[child] = self.set_symlen(sub_table, child, visited)?;
+            }
+        }
+
+        Some(sub_table.symlen[left] + sub_table.symlen[right] + 1)
+    }
+
+    fn symbol_pair(&self, sub_table: &PairsData, symbol: usize) -> (usize, usize) {
+        let node = sub_table.btree + symbol * 3;
+        let bytes: [u8; 3] = self.read_le(node).unwrap_or([0xff; 3]);
+        let [first, middle, last] = bytes.map(usize::from);
+
+        (((middle & 0xf) << 8) | first, (last << 4) | (middle >> 4))
+    }
+
+    fn set_dtz_map(&self, pairs: &mut [PairsData], offset: usize) -> Option<usize> {
+        let mut offset = offset;
+
+        for sub_table in pairs.iter_mut() {
+            if sub_table.flags & FLAG_MAPPED == 0 {
+                continue;
+            }
+
+            if sub_table.flags & FLAG_WIDE != 0 {
+                offset += offset & 1;
+                for map_index in sub_table.map_index.iter_mut() {
+                    *map_index = offset + 2;
+                    offset += 2 * self.u16_le(offset)? + 2;
+                }
+            } else {
+                for map_index in sub_table.map_index.iter_mut() {
+                    *map_index = offset + 1;
+                    offset += self.byte(offset)? + 1;
+                }
+            }
+        }
+
+        Some(offset + (offset & 1))
+    }
+
+    fn block_length(&self, sub_table: &PairsData, block: usize) -> i64 {
+        self.u16_le(sub_table.block_lengths + block * 2)
+            .map_or(0, |length| length as i64)
+    }
+
+    // Value of the position at this index of the sub-table, or `None` if it cannot be read
+    fn decompress_pairs(&self, sub_table: &PairsData, index: u64) -> Option<usize> {
+        if sub_table.flags & FLAG_SINGLE_VALUE != 0 {
+            return Some(usize::from(sub_table.min_sym_len));
+        }
+
+        // Each block stores the values of several positions, and the sparse index points to the
+        // block and offset of the middle of each span of positions
+        let sparse_entry = sub_table.sparse_index + (index / sub_table.span) as usize * 6;
+        let mut block = self.u32_le(sparse_entry).unwrap_or(0);
+        let mut offset = self.u16_le(sparse_entry + 4).unwrap_or(0) as i64;
+        offset += (index % sub_table.span) as i64 - (sub_table.span / 2) as i64;
+
+        while offset < 0 && block > 0 {
+            block -= 1;
+            offset += self.block_length(sub_table, block) + 1;
+        }
+        while offset > self.block_length(sub_table, block) && block < sub_table.num_blocks {
+            offset -= self.block_length(sub_table, block) + 1;
+            block += 1;
+        }
+
+        // Compressed data is read past the end of the block, as if it was padded with zeros
+        let data = self.read_block(sub_table, block)?;
+        let read_be = |offset: usize| {
+            let mut bytes = [0; 8];
+            for (byte, value) in bytes.iter_mut().zip(data.iter().skip(offset)) {
+                *byte = *value;
+            }
+            u64::from_be_bytes(bytes)
+        };
+
+        // Find the symbol that contains the value by skipping the previous symbols of the block
+        let min_sym_len = usize::from(sub_table.min_sym_len);
+        let mut pointer = 0;
+        let mut buffer = read_be(pointer);
+        let mut buffer_size: usize = 64;
+        pointer += 8;
+
+        let mut symbol = loop {
+            let mut length = 0;
+            while length + 1 < sub_table.base64.len() && buffer < sub_table.base64[length] {
+                length += 1;
+            }
+
+            let symbol = buffer
+                .wrapping_sub(sub_table.base64[length])
+                .checked_shr((64 - length - min_sym_len) as u32)
+                .unwrap_or(0) as usize
+                + self.u16_le(sub_table.lowest_sym + length * 2).unwrap_or(0);
+            let symbol_values = sub_table.symlen.get(symbol).map_or(0, |len| *len as i64) + 1;
+
+            if offset < symbol_values {
+                break symbol;
+            }
+
+            offset -= symbol_values;
+            length += min_sym_len;
+            buffer = buffer.checked_shl(length as u32).unwrap_or(0);
+            buffer_size = buffer_size.saturating_sub(length);
+
+            if buffer_size <= 32 {
+                buffer_size += 32;
+                buffer |= (read_be(pointer) >> 32) << (64 - buffer_size);
+                pointer += 4;
+            }
+        };
+
+        // Expand the symbol until the leaf of the value
+        while sub_table.symlen.get(symbol).is_some_and(|len| *len != 0) {
+            let (left, right) = self.symbol_pair(sub_table, symbol);
+            let left_values = sub_table.symlen.get(left).map_or(0, |len| *len as i64) + 1;
+
+            if offset < left_values {
+                symbol = left;
+            } else {
+                offset -= left_values;
+                symbol = right;
+            }
+        }
+
+        Some(self.symbol_pair(sub_table, symbol).0)
+    }
+
+    // DTZ tables only store one side to move, except for symmetric tables without pawns
+    fn has_side_to_move(&self, side: usize, file: usize) -> bool {
+        self.table_type == TableType::Wdl
+            || usize::from(self.sub_table(side, file).flags & FLAG_STM) == side
+            || (self.symmetric && !self.has_pawns)
+    }
+
+    // Distance to zeroing of a DTZ value, in plies, for a position with this WDL
+    fn map_dtz(&self, sub_table: &PairsData, value: usize, wdl: Wdl) -> i32 {
+        let flags = sub_table.flags;
+        let mut value = value;
+
+        if flags & FLAG_MAPPED != 0 {
+            let map = match wdl {
+                Wdl::Win | Wdl::Draw => 0,
+                Wdl::Loss => 1,
+                Wdl::CursedWin => 2,
+                Wdl::BlessedLoss => 3,
+            };
+            let map_index = sub_table.map_index[map];
+            value = if flags & FLAG_WIDE != 0 {
+                self.u16_le(map_index + value * 2).unwrap_or(0)
+            } else {
+                self.byte(map_index + value).unwrap_or(0)
+            };
+        }
+
+        // Stored in moves instead of plies
+        let is_in_moves = match wdl {
+            Wdl::Win => flags & FLAG_WIN_PLIES == 0,
+            Wdl::Loss => flags & FLAG_LOSS_PLIES == 0,
+            Wdl::CursedWin | Wdl::BlessedLoss => true,
+            Wdl::Draw => false,
+        };
+        if is_in_moves {
+            value *= 2;
+        }
+
+        value as i32 + 1
+    }
+
+    /// Value of a position, given by its pieces in ascending order of squares and the player to
+    /// move. `flip` tells if the position has to be flipped, because black has the pieces of
+    /// white in the table name. DTZ tables return the distance to zeroing for a position with
+    /// the `wdl` result, and `None` if the table does not store this player to move or the file
+    /// cannot be read.
+    pub fn probe(
+        &self,
+        pieces: &[(usize, PieceCode)],
+        black_to_move: bool,
+        flip: bool,
+        wdl: Wdl,
+    ) -> Option<i32> {
+        let (side, table_file, index) = self.index(pieces, black_to_move, flip)?;
+        let sub_table = self.sub_table(side, table_file);
+        let value = self.decompress_pairs(sub_table, index)?;
+
+        Some(match self.table_type {
+            TableType::Wdl => value as i32 - 2,
+            TableType::Dtz => self.map_dtz(sub_table, value, wdl),
+        })
+    }
+
+    // Side to move and file of the sub-table of a position, and its index in the sub-table
+    fn index(
+        &self,
+        pieces: &[(usize, PieceCode)],
+        black_to_move: bool,
+        flip: bool,
+    ) -> Option<(usize, usize, u64)> {
+        let tables = &*INDEX_TABLES;
+
+        // Symmetric tables only store white to move
+        let flip = flip || (self.symmetric && black_to_move);
+        let flip_color = if flip { BLACK_PIECE } else { 0 };
+        let flip_squares = if flip { 56 } else { 0 };
+        let side = usize::from(flip ^ black_to_move);
+
+        let mut squares = [0; MAX_PIECES];
+        let mut codes = [0; MAX_PIECES];
+        let mut size = 0;
+        let mut lead_pawns = 0;
+        let mut table_file = 0;
+        let mut lead_pawn_code = None;
+
+        // Tables with pawns are split by the file of the leading pawn, the one closer to the edge
+        // and with the lowest rank
+        if self.has_pawns {
+            let code = self.pairs[0][0].pieces[0] ^ flip_color;
+            lead_pawn_code = Some(code);
+
+            for (square, _) in pieces.iter().filter(|(_, piece)| *piece == code) {
+                squares[size] = square ^ flip_squares;
+                size += 1;
+            }
+            lead_pawns = size;
+
+            let lead = (0..lead_pawns).max_by_key(|i| tables.map_pawns[squares[*i]])?;
+            squares.swap(0, lead);
+            table_file = file(squares[0]).min(7 - file(squares[0]));
+        }
+
+        if !self.has_side_to_move(side, table_file) {
+            return None;
+        }
+
+        for (square, code) in pieces {
+            if Some(*code) != lead_pawn_code {
+                squares[size] = square ^ flip_squares;
+                codes[size] = code ^ flip_color;
+                size += 1;
+            }
+        }
+
+        let sub_table = self.sub_table(side, table_file);
+
+        // Same order of the pieces as in the table
+        for i in lead_pawns..size.saturating_sub(1) {
+            if let Some(j) = (i + 1..size).find(|j| sub_table.pieces[i] == codes[*j]) {
+                codes.swap(i, j);
+                squares.swap(i, j);
+            }
+        }
+
+        // The leading piece or pawn is mirrored to the A1-D1-D4 triangle
+        if file(squares[0]) > 3 {
+            for square in &mut squares[..size] {
+                *square = flip_file(*square);
+            }
+        }
+
+        let mut index = if self.has_pawns {
+            let mut index = tables.lead_pawn_index[lead_pawns][squares[0]];
+            squares[1..lead_pawns].sort_by_key(|square| tables.map_pawns[*square]);
+            for (i, square) in squares[1..lead_pawns].iter().enumerate() {
+                index += tables.binomial[i + 1][tables.map_pawns[*square]];
+            }
+            index
+        } else {
+            if rank(squares[0]) > 3 {
+                for square in &mut squares[..size] {
+                    *square = flip_rank(*square);
+                }
+            }
+
+            // The first piece of the leading group outside of the diagonal goes below it
+            if let Some(i) = (0..sub_table.group_len[0]).find(|i| off_diagonal(squares[*i]) != 0) {
+                if off_diagonal(squares[i]) > 0 {
+                    for square in &mut squares[i..size] {
+                        *square = flip_diagonal(*square);
+                    }
+                }
+            }
+
+            if self.has_unique_pieces {
+                self.unique_pieces_index(&squares)
+            } else {
+                tables.map_kk[tables.map_a1d1d4[squares[0]] as usize][squares[1]]
+            }
+        };
+
+        index *= sub_table.group_index[0];
+
+        // Rest of the groups, the squares of each one in ascending order and skipping the
+        // squares of the previous groups
+        let mut group_start = sub_table.group_len[0];
+        let mut remaining_pawns = self.has_pawns && self.pawn_count[1] > 0;
+        for group in 1.. {
+            let group_len = sub_table.group_len[group];
+            if group_len == 0 {
+                break;
+            }
+
+            squares[group_start..group_start + group_len].sort();
+
+            let mut group_index = 0;
+            for i in 0..group_len {
+                let square = squares[group_start + i];
+                let adjust = squares[..group_start]
+                    .iter()
+                    .filter(|previous| square > **previous)
+                    .count();
+                let pawn_adjust = if remaining_pawns { 8 } else { 0 };
+                group_index += tables.binomial[i + 1][square - adjust - pawn_adjust];
+            }
+
+            remaining_pawns = false;
+            index += group_index * sub_table.group_index[group];
+            group_start += group_len;
+        }
+
+        Some((side, table_file, index))
+    }
+
+    // Index of the first three pieces when they are different
+    fn unique_pieces_index(&self, squares: &[Square; MAX_PIECES]) -> u64 {
+        let tables = &*INDEX_TABLES;
+        let [first, second, third] = [squares[0], squares[1], squares[2]];
+        let adjust1 = u64::from(second > first);
+        let adjust2 = u64::from(third > first) + u64::from(third > second);
+        let rank_of = |square: Square| rank(square) as u64;
+
+        if off_diagonal(first) != 0 {
+            (tables.map_a1d1d4[first] * 63 + (second as u64 - adjust1)) * 62 + third as u64
+                - adjust2
+        } else if off_diagonal(second) != 0 {
+            (6 * 63 + rank_of(first) * 28 + tables.map_b1h1h7[second]) * 62 + third as u64 - adjust2
+        } else if off_diagonal(third) != 0 {
+            6 * 63 * 62
+                + 4 * 28 * 62
+                + rank_of(first) * 7 * 28
+                + (rank_of(second) - adjust1) * 28
+                + tables.map_b1h1h7[third]
+        } else {
+            6 * 63 * 62
+                + 4 * 28 * 62
+                + 4 * 7 * 28
+                + rank_of(first) * 7 * 6
+                + (rank_of(second) - adjust1) * 6
+                + (rank_of(third) - adjust2)
+        }
+    }
+}
+
+#[cfg(test)]
+pub(super) mod tests {
+    use super::*;
+
+    use std::cmp::Reverse;
+    use std::collections::{BinaryHeap, HashSet};
+
+    // Tables are written with blocks of 64 bytes and an entry of the sparse index every 256
+    // positions
+    const BLOCK_SIZE_BITS: u8 = 6;
+    const SPAN_BITS: u8 = 8;
+
+    // Symbol of a single value, or pair of symbols
+    enum Symbol {
+        Leaf(usize),
+        Pair(usize, usize),
+    }
+
+    // Parts of the file of a sub-table
+    #[derive(Default)]
+    struct Encoding {
+        sizes: Vec<u8>,
+        sparse_index: Vec<u8>,
+        block_lengths: Vec<u8>,
+        blocks: Vec<u8>,
+    }
+
+    impl Encoding {
+        fn single_value(flags: u8, value: usize) -> Encoding {
+            Encoding {
+                sizes: vec![flags | FLAG_SINGLE_VALUE, value as u8],
+                ..Default::default()
+            }
+        }
+
+        // Values coded with a canonical Huffman code of symbols of one or more values
+        fn new(flags: u8, values: &[usize]) -> Encoding {
+            if values.iter().all(|value| *value == values[0]) {
+                return Encoding::single_value(flags, values[0]);
+            }
+
+            let mut distinct_values = values.to_vec();
+            distinct_values.sort();
+            distinct_values.dedup();
+
+            // Symbols of each value, of each pair of values, of runs of 4 and 8 values that are
+            // the same and, with few values, of each three values
+            let count = distinct_values.len();
+            let value_id = |value: usize| distinct_values.binary_search(&value).unwrap();
+            let pair_id =
+                |first: usize, second: usize| count + value_id(first) * count + value_id(second);
+            let run_id = |value: usize, long: bool| {
+                count * (count + 1) + value_id(value) * 2 + usize::from(long)
+            };
+
+            let mut symbols = Vec::new();
+            let mut symbol_values = Vec::new();
+            for value in &distinct_values {
+                symbols.push(Symbol::Leaf(*value));
+                symbol_values.push(1);
+            }
+            for first in 0..count {
+                for second in 0..count {
+                    symbols.push(Symbol::Pair(first, second));
+                    symbol_values.push(2);
+                }
+            }
+            for value in &distinct_values {
+                let pair = pair_id(*value, *value);
+                symbols.push(Symbol::Pair(pair, pair));
+                symbol_values.push(4);
+                let run = run_id(*value, false);
+                symbols.push(Symbol::Pair(run, run));
+                symbol_values.push(8);
+            }
+            let has_triples = count <= 8;
+            let triple_id = |first: usize, second: usize, third: usize| {
+                count * (count + 3) + value_id(first) * count * count + pair_id(second, third)
+                    - count
+            };
+            if has_triples {
+                for first in 0..count {
+                    for pair in count..count * (count + 1) {
+                        symbols.push(Symbol::Pair(first, pair));
+                        symbol_values.push(3);
+                    }
+                }
+            }
+
+            let mut coded_symbols = Vec::new();
+            let mut position = 0;
+            while position < values.len() {
+                let value = values[position];
+                let run = values[position..]
+                    .iter()
+                    .take_while(|other| **other == value)
+                    .count();
+                let id = match values[position + 1..] {
+                    _ if run >= 8 => run_id(value, true),
+                    _ if run >= 4 => run_id(value, false),
+                    [second, third, ..] if has_triples => triple_id(value, second, third),
+                    [next, ..] => pair_id(value, next),
+                    [] => value_id(value),
+                };
+                coded_symbols.push(id);
+                position += symbol_values[id];
+            }
+
+            // Every symbol has a code, even if it is not used
+            let mut frequencies = vec![1; symbols.len()];
+            for id in &coded_symbols {
+                frequencies[*id] += 1;
+            }
+            let lengths = code_lengths(&frequencies);
+
+            // The symbols are numbered from the longest codes to the shortest ones
+            let mut order = (0..lengths.len()).collect::<Vec<usize>>();
+            order.sort_by_key(|id| Reverse(lengths[*id]));
+            let mut numbers = vec![0; lengths.len()];
+            for (number, id) in order.iter().enumerate() {
+                numbers[*id] = number;
+            }
+
+            let min_len = *lengths.iter().min().unwrap();
+            let max_len = *lengths.iter().max().unwrap();
+            let sym_lengths = max_len - min_len + 1;
+            let mut counts = vec![0; sym_lengths];
+            for length in &lengths {
+                counts[length - min_len] += 1;
+            }
+            let mut lowest = vec![0; sym_lengths];
+            let mut base = vec![0u64; sym_lengths];
+            for length in (0..sym_lengths - 1).rev() {
+                lowest[length] = lowest[length + 1] + counts[length + 1];
+                base[length] = (base[length + 1] + counts[length + 1] as u64) / 2;
+            }
+            assert_eq!(base[0] + counts[0] as u64, 1 << min_len);
+            assert!(max_len <= 32);
+
+            let mut bits = Vec::new();
+            for id in coded_symbols {
+                let length = lengths[id] - min_len;
+                let code = base[length] + (numbers[id] - lowest[length]) as u64;
+                bits.push((code, lengths[id], symbol_values[id]));
+            }
+
+            // Blocks with the codes that fit, and their number of values
+            let block_bits = 8 << BLOCK_SIZE_BITS;
+            let mut blocks = Vec::new();
+            let mut block_values = Vec::new();
+            let mut used_bits = block_bits;
+            for (code, length, run) in bits {
+                if used_bits + length > block_bits {
+                    blocks.resize(blocks.len() + (1 << BLOCK_SIZE_BITS), 0);
+                    block_values.push(0);
+                    used_bits = 0;
+                }
+
+                let block_start = blocks.len() - (1 << BLOCK_SIZE_BITS);
+                for bit in 0..length {
+                    if code >> (length - 1 - bit) & 1 != 0 {
+                        let position = used_bits + bit;
+                        blocks[block_start + position / 8] |= 0x80 >> (position % 8);
+                    }
+                }
+                used_bits += length;
+                *block_values.last_mut().unwrap() += run;
+            }
+
+            let mut block_starts = vec![0];
+            for count in &block_values {
+                block_starts.push(block_starts.last().unwrap() + count);
+            }
+
+            // Block and offset of the middle position of each span
+            let span = 1 << SPAN_BITS;
+            let mut sparse_index = Vec::new();
+            for span_start in (0..values.len()).step_by(span) {
+                let middle = span_start + span / 2;
+                let block = block_starts[1..]
+                    .iter()
+                    .position(|start| middle < *start)
+                    .unwrap_or(block_values.len() - 1);
+                let offset = middle - block_starts[block];
+                sparse_index.extend((block as u32).to_le_bytes());
+                sparse_index.extend((offset as u16).to_le_bytes());
+            }
+
+            let block_lengths = block_values
+                .iter()
+                .flat_map(|count| (*count as u16 - 1).to_le_bytes())
+                .collect();
+
+            let mut sizes = vec![flags, BLOCK_SIZE_BITS, SPAN_BITS, 0];
+            sizes.extend((block_values.len() as u32).to_le_bytes());
+            sizes.extend([max_len as u8, min_len as u8]);
+            for lowest in lowest {
+                sizes.extend((lowest as u16).to_le_bytes());
+            }
+            sizes.extend((lengths.len() as u16).to_le_bytes());
+
+            for id in order {
+                let (left, right) = match symbols[id] {
+                    Symbol::Leaf(value) => (value, LEAF_SYMBOL),
+                    Symbol::Pair(left, right) => (numbers[left], numbers[right]),
+                };
+                sizes.extend([
+                    left as u8,
+                    ((left >> 8) | ((right & 0xf) << 4)) as u8,
+                    (right >> 4) as u8,
+                ]);
+            }
+            if lengths.len() % 2 == 1 {
+                sizes.push(0);
+            }
+
+            Encoding {
+                sizes,
+                sparse_index,
+                block_lengths,
+                blocks,
+            }
+        }
+    }
+
+    // Lengths of the Huffman code of the symbols with these frequencies
+    fn code_lengths(frequencies: &[usize]) -> Vec<usize> {
+        let mut parents = vec![usize::MAX; frequencies.len()];
+        let mut heap = frequencies
+            .iter()
+            .enumerate()
+            .map(|(id, frequency)| Reverse((*frequency, id)))
+            .collect::<BinaryHeap<_>>();
+
+        while let (Some(Reverse((first, first_id))), Some(Reverse((second, second_id)))) =
+            (heap.pop(), heap.pop())
+        {
+            let id = parents.len();
+            parents.push(usize::MAX);
+            parents[first_id] = id;
+            parents[second_id] = id;
+            heap.push(Reverse((first + second, id)));
+        }
+
+        (0..frequencies.len())
+            .map(|id| {
+                let mut length = 0;
+                let mut node = id;
+                while parents[node] != usize::MAX {
+                    node = parents[node];
+                    length += 1;
+                }
+                length
+            })
+            .collect()
+    }
+
+    fn table_bytes(header: &[u8], encodings: &[Encoding], dtz_map: &[u8]) -> Vec<u8> {
+        let mut bytes = header.to_vec();
+        for encoding in encodings {
+            bytes.extend(&encoding.sizes);
+        }
+        bytes.extend(dtz_map);
+        bytes.resize(bytes.len().next_multiple_of(2), 0);
+        for encoding in encodings {
+            bytes.extend(&encoding.sparse_index);
+        }
+        for encoding in encodings {
+            bytes.extend(&encoding.block_lengths);
+        }
+        for encoding in encodings {
+            bytes.resize(bytes.len().next_multiple_of(64), 0);
+            bytes.extend(&encoding.blocks);
+        }
+        while bytes.len() % 64 != 16 {
+            bytes.push(0);
+        }
+        bytes
+    }
+
+    /// Pieces of a position in ascending order of squares, whether black is to move, and its
+    /// WDL or distance to zeroing.
+    pub(in crate::eval) type TablePosition = (Vec<(usize, PieceCode)>, bool, i32);
+
+    /// Writes a table of a material without pawns, from the value of its positions. DTZ tables
+    /// only store white to move. The positions that are not given take the value of the
+    /// previous index.
+    pub(in crate::eval) fn write_table(
+        path: &Path,
+        name: &str,
+        table_type: TableType,
+        positions: &[TablePosition],
+    ) -> Result<()> {
+        let material = Material::try_from_name(name).unwrap();
+        assert_eq!(material.pawns(0) + material.pawns(1), 0);
+        let symmetric = material.flipped() == material;
+
+        let mut header = match table_type {
+            TableType::Wdl => WDL_MAGIC,
+            TableType::Dtz => DTZ_MAGIC,
+        }
+        .to_vec();
+        header.push(if symmetric { 0 } else { HEADER_SPLIT });
+        // The leading group is the first one for both players to move
+        header.push(0);
+        for player in [0, BLACK_PIECE] {
+            for (_, code) in NAME_PIECES {
+                let count = material.0[usize::from(player != 0)][usize::from(code) - 1];
+                header.extend((0..count).map(|_| (code | player) | ((code | player) << 4)));
+            }
+        }
+        header.resize(header.len().next_multiple_of(2), 0);
+
+        let (sides, flags) = match table_type {
+            TableType::Wdl if !symmetric => (2, 0),
+            TableType::Wdl => (1, 0),
+            TableType::Dtz => (1, FLAG_MAPPED | FLAG_WIN_PLIES | FLAG_LOSS_PLIES),
+        };
+
+        // The index of each position is found with a table of the same pieces
+        let skeleton = (0..sides)
+            .map(|_| Encoding::single_value(flags, 0))
+            .collect::<Vec<Encoding>>();
+        let empty_maps = [0; 4];
+        let dtz_map: &[u8] = match table_type {
+            TableType::Wdl => &[],
+            TableType::Dtz => &empty_maps,
+        };
+        std::fs::write(path, table_bytes(&header, &skeleton, dtz_map))?;
+        let table = Table::open(path, &material, table_type)?;
+
+        // Distances of the wins and the losses, in plies minus one
+        let mut maps = [Vec::new(), Vec::new()];
+        if table_type == TableType::Dtz {
+            for (_, _, dtz) in positions.iter().filter(|(_, _, dtz)| *dtz != 0) {
+                maps[usize::from(*dtz < 0)].push(dtz.unsigned_abs() as u8 - 1);
+            }
+            for map in &mut maps {
+                map.sort();
+                map.dedup();
+            }
+        }
+
+        let mut values = (0..sides)
+            .map(|side| vec![None; table.sub_table(side, 0).size() as usize])
+            .collect::<Vec<Vec<Option<usize>>>>();
+        for (pieces, black_to_move, value) in positions {
+            let Some((side, _, index)) = table.index(pieces, *black_to_move, false) else {
+                continue;
+            };
+
+            let value = match table_type {
+                TableType::Wdl => (value + 2) as usize,
+                TableType::Dtz if *value == 0 => 0,
+                TableType::Dtz => {
+                    let map = &maps[usize::from(*value < 0)];
+                    map.binary_search(&(value.unsigned_abs() as u8 - 1))
+                        .unwrap()
+                }
+            };
+
+            let stored = &mut values[side][index as usize];
+            assert!(
+                stored.is_none_or(|stored| stored == value),
+                "positions with the same index {} have different values",
+                index
+            );
+            *stored = Some(value);
+        }
+
+        let encodings = values
+            .iter()
+            .map(|values| {
+                let mut previous = 0;
+                let values = values
+                    .iter()
+                    .map(|value| {
+                        previous = value.unwrap_or(previous);
+                        previous
+                    })
+                    .collect::<Vec<usize>>();
+                Encoding::new(flags, &values)
+            })
+            .collect::<Vec<Encoding>>();
+
+        let mut dtz_map = Vec::new();
+        if table_type == TableType::Dtz {
+            for map in [&maps[0], &maps[1], &Vec::new(), &Vec::new()] {
+                dtz_map.push(map.len() as u8);
+                dtz_map.extend(map);
+            }
+        }
+        std::fs::write(path, table_bytes(&header, &encodings, &dtz_map))?;
+
+        Ok(())
+    }
+
+    #[test]
+    fn index_tables() {
+        let tables = IndexTables::new();
+
+        // 28 squares below the diagonal, and 10 in the triangle with the diagonal last
+        let below_diagonal = (0..64).filter(|square| off_diagonal(*square) < 0);
+        let codes = below_diagonal
+            .map(|square| tables.map_b1h1h7[square])
+            .collect::<HashSet<u64>>();
+        assert_eq!(codes, (0..28).collect());
+
+        let triangle = [0, 1, 2, 3, 9, 10, 11, 18, 19, 27];
+        let codes = triangle.map(|square| tables.map_a1d1d4[square]);
+        assert_eq!(codes.iter().collect::<HashSet<_>>().len(), 10);
+        assert!(codes.iter().all(|code| *code < 10));
+        assert_eq!(
+            [0, 9, 18, 27].map(|square| tables.map_a1d1d4[square]),
+            [6, 7, 8, 9]
+        );
+
+        // 462 positions of the kings that are not equivalent by symmetry
+        let codes = tables
+            .map_kk
+            .iter()
+            .flatten()
+            .copied()
+            .collect::<HashSet<u64>>();
+        assert_eq!(codes, (0..462).collect());
+
+        assert_eq!(tables.binomial[1][62], 62);
+        assert_eq!(tables.binomial[2][63], 1953);
+        assert_eq!(tables.binomial[3][10], 120);
+        assert_eq!(tables.binomial[4][3], 0);
+
+        // Pawns from A2 to H7, with the squares of the files of the leading pawn first
+        assert_eq!(tables.map_pawns[8], 47);
+        assert_eq!(tables.map_pawns[15], 46);
+        let codes = (8..56)
+            .map(|square| tables.map_pawns[square])
+            .collect::<HashSet<usize>>();
+        assert_eq!(codes, (0..48).collect());
+    }
+
+    #[test]
+    fn material() {
+        let material = Material::try_from_name("KRPvKN").unwrap();
+        assert_eq!(material.count(), 5);
+        assert_eq!(material.pawns(0), 1);
+        assert_eq!(material.pawns(1), 0);
+        assert_eq!(
+            material.flipped(),
+            Material::try_from_name("KNvKRP").unwrap()
+        );
+
+        let mut position_material = Material::default();
+        for code in [6, 4, 1, 6 | BLACK_PIECE, 2 | BLACK_PIECE] {
+            position_material.add(code);
+        }
+        assert_eq!(position_material, material);
+
+        // Invalid names: without both kings, unknown pieces or too many pieces
+        assert!(Material::try_from_name("KQK").is_none());
+        assert!(Material::try_from_name("QvK").is_none());
+        assert!(Material::try_from_name("KXvK").is_none());
+        assert!(Material::try_from_name("KQRBNPvK").is_some());
+        assert!(Material::try_from_name("KQRBNPvKP").is_none());
+    }
+
+    #[test]
+    fn wdl() {
+        assert_eq!(-Wdl::Win, Wdl::Loss);
+        assert_eq!(-Wdl::CursedWin, Wdl::BlessedLoss);
+        assert_eq!(-Wdl::Draw, Wdl::Draw);
+        assert_eq!(Wdl::from_value(-5), Wdl::Loss);
+        assert_eq!(Wdl::from_value(5), Wdl::Win);
+        assert_eq!(Wdl::BlessedLoss.signum(), -1);
+    }
+}
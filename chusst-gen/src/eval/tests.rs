@@ -1,10 +1,21 @@
+use super::feedback::{PeriodicalSearchFeedback, SearchFeedback, SearchIteration};
+use super::history::HashedHistory;
+use super::ordering::{is_quiet, MoveOrdering};
 use super::play::PlayableGame;
+use super::tablebase::tests::write_tables;
+use super::tablebase::Wdl;
+use super::BookPolicy;
+use super::{Branch, GamePrivate, SearchContext, SearchScores};
 use crate::board::{Board, ModifiableBoard, Piece, PieceType, Player, Position};
 use crate::eval::check::SafetyChecks;
-use crate::eval::Game;
+use crate::eval::{
+    evaluate, EngineFeedback, EngineFeedbackMessage, EngineMessage, Game, GameHistory, GameMove,
+    GameResult, HasStopSignal, OpeningBook, Score, SearchLimits, SearchOptions,
+    SilentSearchFeedback, StdoutFeedback, Tablebase, TranspositionTable,
+};
 use crate::game::{
-    CastlingRights, GameState, ModifiableGame, Move, MoveAction, MoveActionType, PromotionPieces,
-    SimpleGame,
+    CastlingRights, GameState, ModifiableGame, Move, MoveAction, MoveActionType, PackedMove,
+    PromotionPieces,
 };
 use crate::{mva, p, pos};
 
@@ -199,7 +210,13 @@ impl GameTestCase {
     }
 }
 
-type TestGame = SimpleGame;
+// The move generation of each board is tested by building with its feature
+#[cfg(feature = "bitboards")]
+type TestGame = crate::game::BitboardGame;
+#[cfg(all(feature = "compact-board", not(feature = "bitboards")))]
+type TestGame = crate::game::CompactGame;
+#[cfg(all(not(feature = "bitboards"), not(feature = "compact-board")))]
+type TestGame = crate::game::SimpleGame;
 
 struct MoveChain<'a> {
     game: &'a mut TestGame,
@@ -231,6 +248,98 @@ fn game_from_fen(fen: &str) -> TestGame {
     .unwrap_or_else(|| panic!("Failed to parse FEN string {}", fen))
 }
 
+// Best branch of the game with the limits and options, with a new history and transposition table
+fn search_with(game: &TestGame, limits: &SearchLimits, options: &SearchOptions) -> Option<Branch> {
+    game.get_best_move_recursive(
+        limits,
+        options,
+        &GameHistory::new(),
+        &TranspositionTable::default(),
+        &mut (),
+        &mut SilentSearchFeedback::default(),
+    )
+}
+
+// Best branch of the game up to the depth, with the default options
+fn search(game: &TestGame, depth: u32) -> Branch {
+    search_with(
+        game,
+        &SearchLimits::from_depth(depth),
+        &SearchOptions::default(),
+    )
+    .expect("no best move found")
+}
+
+// Context of a search of all the moves from a new history
+fn search_context(transposition_table: &TranspositionTable) -> SearchContext<'_> {
+    SearchContext::new(HashedHistory::default(), transposition_table)
+}
+
+// Score of the quiescence search of the game, with a full window
+fn quiescence_score(game: &TestGame) -> Score {
+    game.clone()
+        .get_quiescence_score(
+            0,
+            SearchScores::default(),
+            &mut search_context(&TranspositionTable::new(0)),
+            &mut (),
+        )
+        .score
+}
+
+// Feedback that records the depth and the length of the line of the completed iterations
+#[derive(Default)]
+struct IterationsFeedback {
+    iterations: Vec<(u32, usize)>,
+}
+
+impl SearchFeedback for IterationsFeedback {
+    fn update(&mut self, _nodes: u32) {}
+
+    fn current_move(&mut self, _mv: &MoveAction, _move_number: u32) {}
+
+    fn iteration(&mut self, iteration: &SearchIteration) {
+        // The quiescence search can go deeper than the iteration
+        assert!(iteration.selective_depth >= iteration.depth);
+        self.iterations
+            .push((iteration.depth, iteration.branch.moves.len()));
+    }
+
+    fn info(&mut self, _message: &str) {}
+}
+
+impl std::io::Write for IterationsFeedback {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+// Receiver of the messages of the search feedback
+#[derive(Default)]
+struct FeedbackMessages(std::cell::RefCell<Vec<EngineFeedbackMessage>>);
+
+impl EngineFeedback for FeedbackMessages {
+    fn send(&self, msg: EngineMessage) {
+        if let EngineMessage::SearchFeedback(msg) = msg {
+            self.0.borrow_mut().push(msg);
+        }
+    }
+}
+
+impl std::io::Write for FeedbackMessages {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
 impl From<shakmaty::Square> for Position {
     fn from(pos: shakmaty::Square) -> Self {
         pos!(pos.rank() as usize, pos.file() as usize)
@@ -511,6 +620,8 @@ fn move_reversable() {
         // Do setup moves
         test_case.do_initial_moves();
         let game = &mut test_case.game;
+        game.hash();
+        let game_before_move = game.clone();
 
         // Do move
         assert!(
@@ -521,6 +632,18 @@ fn move_reversable() {
         );
 
         test_case.make_checks();
+
+        // Undo move: the board, castling rights, last move and hash are restored
+        let mut game = game_before_move.clone();
+        let undo = ModifiableGame::do_move_no_checks(&mut game, &test_case.mv).unwrap();
+        game.undo_move(undo);
+        assert_eq!(
+            game,
+            game_before_move,
+            "failed to undo move {} in:\n{}",
+            test_case.mv.mv,
+            game.board()
+        );
     }
 }
 
@@ -813,8 +936,775 @@ fn fen() {
     );
 }
 
+#[test]
+fn iterative_deepening() {
+    // Stop signal that fires after a number of checks
+    struct StopAfter(u32);
+
+    impl HasStopSignal for StopAfter {
+        fn stop(&mut self) -> bool {
+            if self.0 == 0 {
+                return true;
+            }
+            self.0 -= 1;
+            false
+        }
+    }
+
+    let game = TestGame::new();
+    let history = GameHistory::new();
+
+    // Complete search: one iteration per depth
+    let mut feedback = IterationsFeedback::default();
+    let branch = game
+        .get_best_move_recursive(
+            &SearchLimits::from_depth(2),
+            &SearchOptions::default(),
+            &history,
+            &TranspositionTable::default(),
+            &mut (),
+            &mut feedback,
+        )
+        .expect("no best move found");
+    assert_eq!(feedback.iterations, vec![(1, 1), (2, 2), (3, 3)]);
+    assert_eq!(branch.moves.len(), 3);
+
+    // Interrupted search: the result of the last completed iteration is returned
+    let mut feedback = IterationsFeedback::default();
+    let branch = game
+        .get_best_move_recursive(
+            &SearchLimits::from_depth(4),
+            &SearchOptions::default(),
+            &history,
+            &TranspositionTable::default(),
+            &mut StopAfter(500),
+            &mut feedback,
+        )
+        .expect("no best move found");
+    let &(last_depth, last_length) = feedback
+        .iterations
+        .last()
+        .expect("no iteration was completed");
+    assert!(last_depth < 5, "search was not interrupted");
+    assert_eq!(branch.moves.len(), last_length);
+    assert!(game
+        .get_all_possible_moves()
+        .contains(&branch.moves.first().unwrap().mv));
+}
+
+#[test]
+fn periodical_feedback() {
+    let game = TestGame::new();
+    let mut messages = FeedbackMessages::default();
+    game.get_best_move_recursive(
+        &SearchLimits::from_depth(2),
+        &SearchOptions::default(),
+        &GameHistory::new(),
+        &TranspositionTable::default(),
+        &mut (),
+        &mut PeriodicalSearchFeedback::new(std::time::Duration::ZERO, &mut messages),
+    )
+    .expect("no best move found");
+    let messages = messages.0.into_inner();
+
+    // The iterations in progress only report the current move and the nodes
+    let updates = messages.iter().filter(|msg| msg.depth.is_none());
+    assert!(updates.clone().count() > 0);
+    for msg in updates {
+        assert!(msg.score.is_none() && msg.mate.is_none());
+        assert!(msg.principal_variation.is_empty());
+        assert!(msg.current_move.is_some());
+        assert!(msg
+            .current_move_number
+            .is_some_and(|number| (1..=20).contains(&number)));
+    }
+
+    let iterations = messages.iter().filter(|msg| msg.depth.is_some());
+    assert_eq!(
+        iterations.clone().map(|msg| msg.depth).collect::<Vec<_>>(),
+        vec![Some(1), Some(2), Some(3)]
+    );
+    assert!(iterations.clone().all(|msg| msg.score.is_some()));
+
+    // Nodes since the start of the search, in the updates and in the iterations
+    assert!(messages
+        .windows(2)
+        .all(|pair| pair[0].nodes <= pair[1].nodes));
+}
+
+#[test]
+fn search_limits() {
+    // Mate in 1: the search stops as soon as the mate is found
+    let game = game_from_fen("7k/8/8/8/8/1q6/2q5/K7 b - - 0 1");
+    let limits = SearchLimits {
+        depth: 4,
+        mate: Some(1),
+        ..Default::default()
+    };
+    let branch =
+        search_with(&game, &limits, &SearchOptions::default()).expect("no best move found");
+    assert!(branch.result == Some(GameResult::Win(Player::Black)));
+    assert_eq!(branch.moves.len(), 1);
+
+    // Nodes: the search is interrupted before reaching the maximum depth
+    let game = TestGame::new();
+    let limits = SearchLimits {
+        depth: 4,
+        nodes: Some(1000),
+        ..Default::default()
+    };
+    let branch =
+        search_with(&game, &limits, &SearchOptions::default()).expect("no best move found");
+    assert!(branch.searched <= 1000);
+    assert!(branch.moves.len() < 5);
+
+    // Unbounded depth, as when the search is limited by time
+    let limits = SearchLimits {
+        depth: u32::MAX,
+        nodes: Some(1000),
+        ..Default::default()
+    };
+    let branch =
+        search_with(&game, &limits, &SearchOptions::default()).expect("no best move found");
+    assert!(branch.searched <= 1000);
+}
+
+#[test]
+fn static_evaluation() {
+    let score = |fen: &str| i32::from(evaluate(&game_from_fen(fen)));
+
+    // Symmetric positions are balanced for both players
+    assert_eq!(
+        score("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1"),
+        0
+    );
+    assert_eq!(
+        score("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR b KQkq - 0 1"),
+        0
+    );
+
+    // The score is from the point of view of the player to move
+    let white_advantage = score("4k3/8/8/8/8/8/4P3/4K3 w - - 0 1");
+    assert!(white_advantage > 0);
+    assert_eq!(score("4k3/8/8/8/8/8/4P3/4K3 b - - 0 1"), -white_advantage);
+
+    // Centralized knights are better than knights in the corner
+    assert!(score("4k3/8/8/8/3N4/8/8/4K3 w - - 0 1") > score("4k3/8/8/8/8/8/8/N3K3 w - - 0 1"));
+
+    // Passed pawns are better than pawns that can be stopped by enemy pawns
+    assert!(score("4k3/p7/4P3/8/8/8/8/4K3 w - - 0 1") > score("4k3/3p4/4P3/8/8/8/8/4K3 w - - 0 1"));
+
+    // Doubled pawns are worse than pawns in adjacent files
+    assert!(score("4k3/8/8/8/8/3P4/3P4/4K3 w - - 0 1") < score("4k3/8/8/8/8/8/3PP3/4K3 w - - 0 1"));
+}
+
+#[test]
+fn move_ordering() {
+    let game = game_from_fen("4k3/8/8/3p1r2/4P3/8/8/R3K2R w - - 0 1");
+    let mut ordering = MoveOrdering::default();
+    let mut moves = game
+        .get_all_possible_moves()
+        .into_iter()
+        .map(PackedMove::from)
+        .collect::<Vec<PackedMove>>();
+
+    // Quiet moves that caused cutoffs: the last one is the first killer move
+    ordering.add_cutoff(&Player::White, &mva!(a1 => a7).into(), 1, 3);
+    ordering.add_cutoff(&Player::White, &mva!(h1 => h7).into(), 1, 0);
+    // Only history, at another depth
+    ordering.add_cutoff(&Player::White, &mva!(e1 => d2).into(), 2, 1);
+
+    let hash_move = PackedMove::from(mva!(e1 => e2));
+    ordering.sort(
+        game.board(),
+        &Player::White,
+        &mut moves,
+        Some(&hash_move),
+        1,
+    );
+
+    assert!(moves[0] == hash_move);
+    // Rook captured before pawn
+    assert!(moves[1] == mva!(e4 => f5).into());
+    assert!(moves[2] == mva!(e4 => d5).into());
+    // Killer moves, most recent first
+    assert!(moves[3] == mva!(h1 => h7).into());
+    assert!(moves[4] == mva!(a1 => a7).into());
+    // History heuristic
+    assert!(moves[5] == mva!(e1 => d2).into());
+
+    // En passant is a capture
+    let mut game = game_from_fen("4k3/8/8/8/3p4/8/4P3/4K3 w - - 0 1");
+    MoveChain::new(&mut game).do_move(tm!(e2 => e4));
+    assert!(!is_quiet(game.board(), &mva!(d4 => e3).into()));
+    assert!(is_quiet(game.board(), &mva!(d4 => d3).into()));
+}
+
+#[test]
+fn selective_search() {
+    // The hash after a null move is the same as the hash calculated from scratch, without the
+    // en passant file of the last move
+    let mut game = TestGame::new();
+    game.hash();
+    assert!(game.do_move(&mva!(e2 => e4)).is_some());
+    game.do_null_move();
+    assert_eq!(game.player(), Player::White);
+    assert!(game.last_move().is_none());
+    assert_eq!(game.cached_hash(), Some(game.clone_unhashed().hash()));
+
+    // Quiet mate in 2 that must not be hidden by the reductions
+    let branch = search(&game_from_fen("7k/8/8/8/8/8/R7/1R4K1 w - - 0 1"), 4);
+    assert!(branch.result == Some(GameResult::Win(Player::White)));
+    assert_eq!(branch.score.mate_moves(), Some(2));
+}
+
+#[test]
+fn search_extensions() {
+    // A check at the last ply is searched until the mate
+    let branch = search(&game_from_fen("6k1/5ppp/8/8/8/8/5PPP/R5K1 w - - 0 1"), 0);
+    assert!(branch.moves.first().unwrap().mv == mva!(a1 => a8));
+    assert_eq!(branch.score.mate_moves(), Some(1));
+
+    // Mate in 2 with a search of 2 plies: the check and the only reply are extended
+    let branch = search(&game_from_fen("r5k1/5ppp/8/8/8/8/4RPPP/4R1K1 w - - 0 1"), 1);
+    assert!(branch.moves.first().unwrap().mv == mva!(e2 => e8));
+    assert_eq!(branch.score.mate_moves(), Some(2));
+}
+
+#[test]
+fn aspiration_windows() {
+    let mut game = game_from_fen("r3k3/8/8/3p4/8/2N5/8/3QK3 w - - 0 1");
+    game.hash();
+
+    // Without transposition table, so that the searches are independent
+    let transposition_table = TranspositionTable::new(0);
+    let mut search = |expected_score: Option<Score>| {
+        game.get_best_move_aspiration(
+            2,
+            expected_score,
+            &mut search_context(&transposition_table),
+            &mut (),
+            &mut SilentSearchFeedback::default(),
+        )
+        .branch
+        .expect("no best move found")
+    };
+
+    let full_window = search(None);
+
+    // The window is widened until it contains the score, both on fail-low and fail-high
+    for expected_score in [
+        full_window.score,
+        full_window.score + Score::from(2000),
+        full_window.score - Score::from(2000),
+    ] {
+        let branch = search(Some(expected_score));
+        assert!(branch.score == full_window.score);
+        assert!(branch.moves.first().unwrap().mv == full_window.moves.first().unwrap().mv);
+    }
+
+    // The failed searches are counted
+    assert!(search(Some(full_window.score + Score::from(2000))).searched > full_window.searched);
+}
+
+#[test]
+fn principal_variation_search() {
+    let search = |game: &TestGame, principal_variation_search: bool| {
+        let options = SearchOptions {
+            principal_variation_search,
+            ..Default::default()
+        };
+        search_with(game, &SearchLimits::from_depth(3), &options).expect("no best move found")
+    };
+
+    // Same result as plain alpha-beta
+    for fen in [
+        "r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 0 1",
+        "r3k3/8/8/3p4/8/2N5/8/3QK3 w - - 0 1",
+        "r5k1/5ppp/8/8/8/8/4RPPP/4R1K1 w - - 0 1",
+    ] {
+        let game = game_from_fen(fen);
+        let alpha_beta = search(&game, false);
+        let pvs = search(&game, true);
+        assert!(pvs.score == alpha_beta.score, "different score in {}", fen);
+        assert!(
+            pvs.moves.first().unwrap().mv == alpha_beta.moves.first().unwrap().mv,
+            "different move in {}",
+            fen
+        );
+    }
+}
+
+#[test]
+fn static_exchange() {
+    let see = |fen: &str, mv: MoveAction| i32::from(game_from_fen(fen).static_exchange(&mv));
+
+    // Undefended piece
+    assert_eq!(see("4k3/8/8/3r4/8/8/8/3RK3 w - - 0 1", mva!(d1 => d5)), 500);
+    // Defended piece of the same value
+    assert_eq!(see("4k3/8/4p3/3r4/8/8/8/3RK3 w - - 0 1", mva!(d1 => d5)), 0);
+    // Defended pawn captured by the queen
+    assert_eq!(
+        see("4k3/2p5/3p4/8/8/8/8/3QK3 w - - 0 1", mva!(d1 => d6)),
+        -800
+    );
+    // The rook behind the queen recaptures after the queen
+    assert_eq!(
+        see("3rk3/8/3p4/4P3/8/8/8/3QK3 w - - 0 1", mva!(e5 => d6)),
+        100
+    );
+    // Pawn defended by a rook, attacked by a knight and a rook behind it
+    assert_eq!(
+        see("3rk3/8/3p4/8/4N3/8/8/3RK3 w - - 0 1", mva!(e4 => d6)),
+        100
+    );
+    // The king recaptures, unless the piece is defended
+    assert_eq!(
+        see("8/8/8/4k3/3p4/8/1B6/4K3 w - - 0 1", mva!(b2 => d4)),
+        -200
+    );
+    assert_eq!(
+        see("8/8/8/4k3/3p4/8/1B6/Q3K3 w - - 0 1", mva!(b2 => d4)),
+        100
+    );
+    // Quiet move to a square attacked by a pawn
+    assert_eq!(
+        see("4k3/8/4p3/8/3N4/8/8/4K3 w - - 0 1", mva!(d4 => f5)),
+        -300
+    );
+    // En passant
+    assert_eq!(
+        see("4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 1", mva!(e5 => d6)),
+        100
+    );
+}
+
+#[test]
+fn tablebase() {
+    let directory = std::env::temp_dir().join(format!("chusst-tablebase-{}", std::process::id()));
+    std::fs::create_dir_all(&directory).unwrap();
+    let path = directory.to_str().unwrap();
+
+    assert!(Tablebase::open(&format!("{}/missing", path)).is_err());
+
+    let tablebase = Tablebase::open(path).unwrap();
+    assert!(tablebase.is_empty());
+    assert_eq!(tablebase.max_pieces(), 0);
+
+    // Invalid files are not opened, and only the files of tables are read
+    std::fs::write(directory.join("KQvK.rtbw"), [0u8; 80]).unwrap();
+    assert!(Tablebase::open(path).is_err());
+    std::fs::write(directory.join("KRvK.txt"), [0u8; 80]).unwrap();
+    write_tables(&directory);
+    let tablebase = Tablebase::open(path).unwrap();
+
+    assert_eq!(tablebase.len(), 2);
+    assert_eq!(tablebase.max_pieces(), 3);
+
+    let kings = game_from_fen("8/8/4k3/8/8/3K4/8/8 w - - 0 1");
+    assert!(tablebase.probe_wdl(&kings) == Some(Wdl::Draw));
+    assert!(tablebase.root_dtz(&kings, &kings.get_all_possible_moves()) == Some(vec![0; 8]));
+
+    // Positions with more pieces or castling rights are not probed
+    let queen = game_from_fen("8/8/4k3/8/8/3K4/8/7Q w - - 0 1");
+    assert!(tablebase.probe_wdl(&queen) == Some(Wdl::Win));
+    assert!(tablebase
+        .probe_wdl(&game_from_fen("8/8/4k3/8/8/3K4/8/6QR w - - 0 1"))
+        .is_none());
+    assert!(tablebase
+        .probe_wdl(&game_from_fen("4k3/8/8/8/8/8/8/4K2R w K - 0 1"))
+        .is_none());
+
+    // The root moves are chosen by the tables, which win without a mate in sight
+    let options = SearchOptions {
+        tablebase: Some(std::sync::Arc::new(tablebase)),
+        ..Default::default()
+    };
+    let branch =
+        search_with(&queen, &SearchLimits::from_depth(2), &options).expect("no best move found");
+    assert!(branch.score > Score::from(50_000));
+    assert!(!branch.score.is_mate());
+
+    // Draws score above the losses, both at the root and after a capture into the tables
+    let draws = [
+        // Capturing the rook is the only move that does not lose
+        ("8/8/4k3/3R4/8/8/8/7K b - - 0 1", mva!(e6 => d5)),
+        // The rook is captured back after capturing the queen, else the queen is kept
+        ("7K/8/8/4k3/3q4/8/8/3R4 w - - 0 1", mva!(d1 => d4)),
+    ];
+    for (fen, best_move) in draws {
+        let branch = search_with(&game_from_fen(fen), &SearchLimits::from_depth(3), &options)
+            .expect("no best move found");
+        assert!(branch.moves.first().unwrap().mv == best_move);
+        assert!(branch.score == Score::from(0));
+    }
+
+    std::fs::remove_dir_all(&directory).unwrap();
+}
+
+#[test]
+fn polyglot_hash() {
+    // Reference keys of the Polyglot book format
+    let mut game = TestGame::new();
+    assert_eq!(u64::from(game.polyglot_hash()), 0x463b96181691fc9c);
+
+    let moves_and_keys = [
+        (mva!(e2 => e4), 0x823c9b50fd114196),
+        (mva!(d7 => d5), 0x0756b94461c50fb0),
+        (mva!(e4 => e5), 0x662fafb965db29d4),
+        // En passant is possible
+        (mva!(f7 => f5), 0x22a48b5a8e47ff78),
+        (mva!(e1 => e2), 0x652a607ca3f242c1),
+        (mva!(e8 => f7), 0x00fdd303c946bdd9),
+    ];
+    for (mv, key) in moves_and_keys {
+        MoveChain::new(&mut game).do_move(TestMove::AsMove(mv));
+        assert_eq!(u64::from(game.polyglot_hash()), key, "after {}", mv.mv);
+    }
+
+    let mut game = TestGame::new();
+    let moves_and_keys = [
+        (mva!(a2 => a4), None),
+        (mva!(b7 => b5), None),
+        (mva!(h2 => h4), None),
+        (mva!(b5 => b4), None),
+        (mva!(c2 => c4), Some(0x3c8123ea7b067637)),
+        (mva!(b4 => c3), None),
+        (mva!(a1 => a3), Some(0x5c3f9b829b279560)),
+    ];
+    for (mv, key) in moves_and_keys {
+        MoveChain::new(&mut game).do_move(TestMove::AsMove(mv));
+        if let Some(key) = key {
+            assert_eq!(u64::from(game.polyglot_hash()), key, "after {}", mv.mv);
+        }
+    }
+}
+
+#[test]
+fn opening_book() {
+    let path = std::env::temp_dir().join(format!("chusst-book-{}.bin", std::process::id()));
+    let path = path.to_str().unwrap();
+
+    assert!(OpeningBook::open(path).is_err());
+
+    // Key, move, weight and learning data of each entry
+    let entry = |key: u64, mv: u16, weight: u16| {
+        [
+            key.to_be_bytes().as_slice(),
+            &mv.to_be_bytes(),
+            &weight.to_be_bytes(),
+            &[0; 4],
+        ]
+        .concat()
+    };
+    let start_key = 0x463b96181691fc9c;
+    let e2e4 = (1 << 9) | (4 << 6) | (3 << 3) | 4;
+    let d2d4 = (1 << 9) | (3 << 6) | (3 << 3) | 3;
+    let e2e5 = (1 << 9) | (4 << 6) | (4 << 3) | 4;
+    let book_data = [
+        entry(start_key, d2d4, 5),
+        entry(start_key, e2e4, 10),
+        // Illegal moves and moves without weight are never played
+        entry(start_key, e2e5, 100),
+        entry(start_key, 0, 0),
+    ]
+    .concat();
+
+    std::fs::write(path, &book_data[..20]).unwrap();
+    assert!(OpeningBook::open(path).is_err());
+
+    std::fs::write(path, &book_data).unwrap();
+    let book = std::sync::Arc::new(OpeningBook::open(path).unwrap());
+    std::fs::remove_file(path).unwrap();
+
+    assert_eq!(book.len(), 4);
+
+    let game = TestGame::new();
+    assert_eq!(book.probe(&game, BookPolicy::Best), Some(mva!(e2 => e4)));
+    for _ in 0..20 {
+        let mv = book.probe(&game, BookPolicy::Weighted).unwrap();
+        assert!(mv == mva!(e2 => e4) || mv == mva!(d2 => d4));
+    }
+
+    let mut game_after_move = TestGame::new();
+    MoveChain::new(&mut game_after_move).do_move(tm!(e2 => e4));
+    assert!(book.probe(&game_after_move, BookPolicy::Best).is_none());
+
+    // The book move is played without searching, unless it is not one of the searched moves
+    let book_move = |search_moves: Vec<MoveAction>| {
+        game.get_best_move_with_logger(
+            &SearchLimits {
+                depth: 1,
+                search_moves,
+                ..Default::default()
+            },
+            &SearchOptions {
+                book: Some(book.clone()),
+                book_policy: BookPolicy::Best,
+                ..Default::default()
+            },
+            &GameHistory::new(),
+            &TranspositionTable::default(),
+            &mut (),
+            &mut StdoutFeedback::default(),
+        )
+    };
+    assert!(matches!(book_move(Vec::new()), GameMove::Normal(mv) if mv == mva!(e2 => e4)));
+    assert!(
+        matches!(book_move(vec![mva!(g1 => f3)]), GameMove::Normal(mv) if mv == mva!(g1 => f3))
+    );
+}
+
+#[test]
+fn quiescence_search() {
+    let best_move = |fen: &str| search(&game_from_fen(fen), 0).moves.first().unwrap().mv;
+
+    // The pawn is defended: capturing it loses the queen
+    assert!(best_move("4k3/8/4p3/3p4/8/8/8/3QK3 w - - 0 1") != mva!(d1 => d5));
+
+    // The pawn is not defended
+    assert!(best_move("4k3/8/8/3p4/8/8/8/3QK3 w - - 0 1") == mva!(d1 => d5));
+
+    // En passant captures are searched
+    let mut game = game_from_fen("4k3/8/8/8/3p4/8/4P3/4K3 w - - 0 1");
+    MoveChain::new(&mut game).do_move(tm!(e2 => e4));
+    assert!(quiescence_score(&game) > evaluate(&game) + Score::from(50));
+
+    // In check there is no stand pat: every evasion loses the queen to the checking knight
+    let game = game_from_fen("k7/8/8/2Q5/8/3n4/8/4K3 w - - 0 1");
+    assert!(quiescence_score(&game) < Score::from(0));
+
+    // In check and without evasions
+    let game = game_from_fen("7k/6Q1/6K1/8/8/8/8/8 b - - 0 1");
+    assert!(quiescence_score(&game) == -Score::mate_in(0));
+}
+
+#[test]
+fn mate_scores() {
+    // Faster mates score higher, and any mate is better than anything else
+    assert!(Score::mate_in(1) > Score::mate_in(3));
+    assert!(Score::mate_in(99) > Score::from(100_000));
+    assert!(-Score::mate_in(2) < -Score::mate_in(4));
+    assert!(-Score::mate_in(99) < Score::stalemate());
+
+    assert_eq!(Score::mate_in(1).mate_moves(), Some(1));
+    assert_eq!(Score::mate_in(3).mate_moves(), Some(2));
+    assert_eq!((-Score::mate_in(2)).mate_moves(), Some(-1));
+    assert_eq!((-Score::mate_in(4)).mate_moves(), Some(-2));
+    assert_eq!(Score::from(900).mate_moves(), None);
+    assert_eq!(Score::stalemate().mate_moves(), None);
+
+    // The fastest mate is preferred even if the search is deeper
+    let branch = search(&game_from_fen("7k/8/8/8/8/1q6/2q5/K7 b - - 0 1"), 3);
+    assert_eq!(branch.score.mate_moves(), Some(1));
+    assert_eq!(branch.moves.len(), 1);
+}
+
+#[test]
+fn fail_low_ties() {
+    let search_move = |game: &TestGame, depth: u32, mv: MoveAction| {
+        let limits = SearchLimits {
+            depth,
+            search_moves: vec![mv],
+            ..Default::default()
+        };
+        search_with(game, &limits, &SearchOptions::default()).expect("no best move found")
+    };
+
+    // Moves that fail low score the bound with a shorter line, and must not replace the best
+    // move with the same score: the best move scores the same when searched alone
+    for (fen, depth) in [
+        (
+            "r1bqkbnr/pppp1ppp/2n5/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R w KQkq - 0 1",
+            4,
+        ),
+        (
+            "rnbqkb1r/pp1p1ppp/4pn2/2p5/2PP4/2N5/PP2PPPP/R1BQKBNR w KQkq - 0 1",
+            3,
+        ),
+    ] {
+        let game = game_from_fen(fen);
+        let branch = search(&game, depth);
+        let best_move = branch.moves.first().unwrap().mv;
+        assert!(search_move(&game, depth, best_move).score == branch.score);
+    }
+
+    // The rook move scores lower than the knight move
+    let game = game_from_fen("rnbqkb1r/pp1p1ppp/4pn2/2p5/2PP4/2N5/PP2PPPP/R1BQKBNR w KQkq - 0 1");
+    let branch = search(&game, 3);
+    assert!(branch.moves.first().unwrap().mv != mva!(a1 => b1));
+    assert!(branch.score >= search_move(&game, 3, mva!(g1 => f3)).score);
+}
+
+#[test]
+fn multi_pv() {
+    let history = GameHistory::new();
+    let search = |game: &TestGame, multi_pv: usize| {
+        game.get_best_branches_recursive(
+            &SearchLimits::from_depth(1),
+            &SearchOptions {
+                multi_pv,
+                ..Default::default()
+            },
+            &history,
+            &TranspositionTable::default(),
+            &mut (),
+            &mut SilentSearchFeedback::default(),
+        )
+    };
+
+    let game = TestGame::new();
+    let branches = search(&game, 3);
+    assert_eq!(branches.len(), 3);
+    for (index, branch) in branches.iter().enumerate() {
+        // Different moves, best first
+        let first_move = branch.moves.first().unwrap().mv;
+        assert!(branches[..index]
+            .iter()
+            .all(|previous| previous.moves.first().unwrap().mv != first_move));
+        assert!(index == 0 || branches[index - 1].score >= branch.score);
+    }
+
+    // The best line is the same as without multi-PV
+    let single_branch = search(&game, 1);
+    assert_eq!(single_branch.len(), 1);
+    assert!(single_branch[0].score == branches[0].score);
+
+    // Not enough legal moves
+    let game = game_from_fen("K7/8/1k6/8/8/8/8/8 w - - 0 1");
+    let branches = search(&game, 5);
+    assert_eq!(branches.len(), 1);
+    assert!(branches[0].moves.first().unwrap().mv == mva!(a8 => b8));
+}
+
+#[test]
+fn search_moves() {
+    let search = |game: &TestGame, search_moves: Vec<MoveAction>| {
+        let limits = SearchLimits {
+            depth: 2,
+            search_moves,
+            ..Default::default()
+        };
+        search_with(game, &limits, &SearchOptions::default())
+    };
+
+    // The queen can be captured, but only the king moves are searched
+    let game = game_from_fen("4k3/8/8/3q4/8/8/8/3QK3 w - - 0 1");
+    let branch = search(&game, vec![]).expect("no best move found");
+    assert!(branch.moves.first().unwrap().mv == mva!(d1 => d5));
+
+    let candidates = vec![mva!(e1 => e2), mva!(e1 => f2)];
+    let branch = search(&game, candidates.clone()).expect("no best move found");
+    assert!(candidates.contains(&branch.moves.first().unwrap().mv));
+
+    // None of the moves is legal
+    assert!(search(&game, vec![mva!(e1 => e3)]).is_none());
+}
+
+#[test]
+fn parallel_search() {
+    let options = SearchOptions {
+        threads: 4,
+        ..Default::default()
+    };
+    let search = |game: &TestGame, depth: u32| {
+        search_with(game, &SearchLimits::from_depth(depth), &options).expect("no best move found")
+    };
+
+    let game = game_from_fen("7k/8/8/8/8/1q6/2q5/K7 b - - 0 1");
+    let branch = search(&game, 2);
+    assert!(branch.result == Some(GameResult::Win(Player::Black)));
+
+    let game = TestGame::new();
+    let branch = search(&game, 3);
+    assert!(game
+        .get_all_possible_moves()
+        .contains(&branch.moves.first().unwrap().mv));
+}
+
+#[test]
+fn transposition_table() {
+    let history = GameHistory::new();
+    let mut game = TestGame::new();
+    let transposition_table = TranspositionTable::new(1);
+
+    let branch = game
+        .get_best_move_recursive(
+            &SearchLimits::from_depth(2),
+            &SearchOptions::default(),
+            &history,
+            &transposition_table,
+            &mut (),
+            &mut SilentSearchFeedback::default(),
+        )
+        .expect("no best move found");
+
+    // The root position is stored with the best move of the deepest iteration
+    let entry = transposition_table
+        .get(&game.hash())
+        .expect("root position not found");
+    assert_eq!(entry.depth, 2);
+    assert!(entry.best_move == Some(PackedMove::from(branch.moves.first().unwrap().mv)));
+
+    // Searching again with the same table gives the same result
+    let cached_branch = game
+        .get_best_move_recursive(
+            &SearchLimits::from_depth(2),
+            &SearchOptions::default(),
+            &history,
+            &transposition_table,
+            &mut (),
+            &mut SilentSearchFeedback::default(),
+        )
+        .expect("no best move found");
+    assert!(cached_branch.moves.first() == branch.moves.first());
+    assert!(cached_branch.score == branch.score);
+    assert_eq!(cached_branch.moves.len(), branch.moves.len());
+
+    // The mates found through the table stop the search with the whole line
+    let mut game = game_from_fen("7k/8/8/8/8/8/R7/1R4K1 w - - 0 1");
+    let limits = SearchLimits {
+        depth: 6,
+        mate: Some(2),
+        ..Default::default()
+    };
+    for _ in 0..2 {
+        let mut feedback = IterationsFeedback::default();
+        let branch = game
+            .get_best_move_recursive(
+                &limits,
+                &SearchOptions::default(),
+                &history,
+                &transposition_table,
+                &mut (),
+                &mut feedback,
+            )
+            .expect("no best move found");
+        assert!(branch.result == Some(GameResult::Win(Player::White)));
+        assert_eq!(branch.moves.len(), 3);
+        assert!(
+            feedback.iterations.len() < 7,
+            "search was not stopped by the mate"
+        );
+    }
+
+    // A disabled table stores nothing
+    let disabled_table = TranspositionTable::new(0);
+    assert!(disabled_table.is_empty());
+    game.get_best_move_recursive(
+        &SearchLimits::from_depth(1),
+        &SearchOptions::default(),
+        &history,
+        &disabled_table,
+        &mut (),
+        &mut SilentSearchFeedback::default(),
+    )
+    .expect("no best move found");
+    assert!(disabled_table.get(&game.hash()).is_none());
+}
+
 fn perft_impl(force_comparison: bool) {
-    fn mv_rec(game: &TestGame, depth: u8) -> u64 {
+    fn mv_rec(game: &mut TestGame, depth: u8) -> u64 {
         if depth == 0 {
             return 1;
         }
@@ -825,23 +1715,23 @@ fn perft_impl(force_comparison: bool) {
                 nodes += 1;
                 continue;
             }
-            let mut game_copy = game.clone();
-            game_copy.do_move(&mv);
-            nodes += mv_rec(&game_copy, depth - 1);
+            let undo = ModifiableGame::do_move_no_checks(game, &mv).unwrap();
+            nodes += mv_rec(game, depth - 1);
+            game.undo_move(undo);
         }
 
         nodes
     }
 
     let assert_perft = |fen: &str, name: &str, depth: u8, expected: u64| {
-        let game = game_from_fen(fen);
+        let mut game = game_from_fen(fen);
 
         if force_comparison {
             perft_compare_against_shakmaty(fen, depth);
             return;
         }
 
-        let nodes = mv_rec(&game, depth);
+        let nodes = mv_rec(&mut game, depth);
         if nodes != expected {
             println!(
                 "Perft {} depth {} expected {}, got {}",
@@ -906,6 +1796,29 @@ fn perft_slow() {
     perft_impl(true);
 }
 
+#[test]
+fn legal_moves() {
+    // Pinned knight and bishop
+    perft_compare_against_shakmaty("4k3/4r3/8/8/1q6/8/3BN3/4K3 w - - 0 1", 3);
+
+    // Double check, only the king can move
+    perft_compare_against_shakmaty("4k3/8/8/8/8/5n2/8/r3K3 w - - 0 1", 2);
+
+    // Check by a sliding piece, which can be blocked
+    perft_compare_against_shakmaty("4k3/8/8/8/8/8/1N1R4/r3K3 w - - 0 1", 3);
+
+    // The king cannot step away from a sliding piece along the line of its check
+    perft_compare_against_shakmaty("4k3/8/8/8/1b6/8/3K4/8 w - - 0 1", 3);
+    perft_compare_against_shakmaty("4k3/8/8/8/8/8/8/r2K4 w - - 0 1", 3);
+
+    // Castling out of, through and into check
+    perft_compare_against_shakmaty("r3k2r/8/8/8/8/8/6b1/R3K2R w KQkq - 0 1", 3);
+    perft_compare_against_shakmaty("r3k2r/8/8/8/8/4r3/8/R3K2R w KQkq - 0 1", 3);
+
+    // En passant that exposes the king along the rank
+    perft_compare_against_shakmaty("8/2p5/8/KP5r/8/8/8/7k b - - 0 1", 3);
+}
+
 // Template to quickly test a specific board/move
 #[test]
 #[ignore]
@@ -0,0 +1,129 @@
+use super::{GameResult, Score};
+use crate::game::{GameHash, PackedMove};
+
+use std::sync::Mutex;
+
+#[derive(Copy, Clone, PartialEq)]
+pub enum Bound {
+    // The score is exact
+    Exact,
+    // The score is at least this value (beta cutoff)
+    Lower,
+    // The score is at most this value (no move improved alpha)
+    Upper,
+}
+
+#[derive(Copy, Clone)]
+pub struct TranspositionEntry {
+    pub hash: GameHash,
+    // Remaining depth of the search below this position
+    pub depth: u32,
+    pub bound: Bound,
+    pub score: Score,
+    pub best_move: Option<PackedMove>,
+    // Result of the best line, if it ends the game
+    pub result: Option<GameResult>,
+}
+
+// Mate scores are stored as the distance from the position, not from the root of the search,
+// so that they are still valid when the position is reached through a different path.
+pub(super) fn score_to_table(score: Score, current_depth: u32) -> Score {
+    let plies = Score::from(current_depth as i32);
+    match score.mate_plies() {
+        Some(_) if score > Score::from(0) => score + plies,
+        Some(_) => score - plies,
+        None => score,
+    }
+}
+
+pub(super) fn score_from_table(score: Score, current_depth: u32) -> Score {
+    let plies = Score::from(current_depth as i32);
+    match score.mate_plies() {
+        Some(_) if score > Score::from(0) => score - plies,
+        Some(_) => score + plies,
+        None => score,
+    }
+}
+
+/// Fixed-size hash table of searched positions, indexed by their Zobrist hash.
+///
+/// Each entry has its own lock, so the table can be shared by several search threads.
+pub struct TranspositionTable {
+    entries: Vec<Mutex<Option<TranspositionEntry>>>,
+}
+
+impl TranspositionTable {
+    pub const DEFAULT_SIZE_MB: usize = 16;
+
+    pub fn new(size_mb: usize) -> Self {
+        let capacity =
+            size_mb * 1024 * 1024 / std::mem::size_of::<Mutex<Option<TranspositionEntry>>>();
+
+        TranspositionTable {
+            entries: (0..capacity).map(|_| Mutex::new(None)).collect(),
+        }
+    }
+
+    pub fn clear(&mut self) {
+        for entry in &mut self.entries {
+            *entry.get_mut().unwrap() = None;
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Occupation of the table, in permille, estimated from the first entries.
+    pub fn hash_full(&self) -> u32 {
+        let sample = self.entries.len().min(1000);
+        if sample == 0 {
+            return 0;
+        }
+
+        let used = self.entries[..sample]
+            .iter()
+            .filter(|entry| entry.lock().unwrap().is_some())
+            .count();
+
+        (used * 1000 / sample) as u32
+    }
+
+    fn index(&self, hash: &GameHash) -> Option<usize> {
+        if self.entries.is_empty() {
+            return None;
+        }
+        Some((u64::from(*hash) % self.entries.len() as u64) as usize)
+    }
+
+    pub fn get(&self, hash: &GameHash) -> Option<TranspositionEntry> {
+        let slot = self.entries[self.index(hash)?].lock().unwrap();
+        slot.filter(|entry| entry.hash == *hash)
+    }
+
+    pub fn insert(&self, entry: TranspositionEntry) {
+        let Some(index) = self.index(&entry.hash) else {
+            return;
+        };
+        let mut slot = self.entries[index].lock().unwrap();
+
+        // Keep the deeper search of the same position, otherwise always replace
+        if let Some(current) = slot.as_ref() {
+            if current.hash == entry.hash && current.depth > entry.depth {
+                return;
+            }
+        }
+
+        *slot = Some(entry);
+    }
+}
+
+impl Default for TranspositionTable {
+    fn default() -> Self {
+        TranspositionTable::new(Self::DEFAULT_SIZE_MB)
+    }
+}
@@ -1,3 +1,4 @@
+mod packed;
 mod play;
 mod zobrist;
 
@@ -9,6 +10,8 @@ use serde::Serialize;
 
 use crate::board::{Board, ModifiableBoard, Piece, PieceType, Player, Position, SimpleBoard};
 use crate::{mv, pos};
+pub use packed::PackedMove;
+pub use zobrist::PolyglotHash;
 pub use zobrist::ZobristHash as GameHash;
 pub use zobrist::ZobristHashBuilder as GameHashBuilder;
 
@@ -42,7 +45,7 @@ impl PromotionPieces {
 
     pub fn try_from_char(value: char) -> Option<Self> {
         match value.to_ascii_lowercase() {
-            'k' => Some(PromotionPieces::Knight),
+            'n' | 'k' => Some(PromotionPieces::Knight),
             'b' => Some(PromotionPieces::Bishop),
             'r' => Some(PromotionPieces::Rook),
             'q' => Some(PromotionPieces::Queen),
@@ -62,13 +65,13 @@ impl From<PromotionPieces> for PieceType {
     }
 }
 
-#[derive(Copy, Clone, PartialEq)]
+#[derive(Copy, Clone, Debug, PartialEq)]
 pub enum MoveActionType {
     Normal,
     Promotion(PromotionPieces),
 }
 
-#[derive(Copy, Clone, PartialEq)]
+#[derive(Copy, Clone, Debug, PartialEq)]
 pub struct MoveAction {
     pub mv: Move,
     pub move_type: MoveActionType,
@@ -98,6 +101,17 @@ impl MoveAction {
             _ => None,
         }
     }
+
+    pub fn to_long_algebraic_string(&self) -> String {
+        let promotion = match self.move_type {
+            MoveActionType::Normal => "",
+            MoveActionType::Promotion(PromotionPieces::Knight) => "n",
+            MoveActionType::Promotion(PromotionPieces::Bishop) => "b",
+            MoveActionType::Promotion(PromotionPieces::Rook) => "r",
+            MoveActionType::Promotion(PromotionPieces::Queen) => "q",
+        };
+        format!("{}{}{}", self.mv.source, self.mv.target, promotion)
+    }
 }
 
 #[macro_export]
@@ -178,6 +192,31 @@ pub struct MoveInfo {
     pub info: MoveExtraInfo,
 }
 
+/// Changes made by a move, to undo it with [`ModifiableGame::undo_move`].
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct MoveUndo {
+    mv: Move,
+    // Piece before the move, which is different from the piece on the target after a promotion
+    moved_piece: Piece,
+    // Captured piece and its position, which is not the target with en passant
+    captured: Option<(Position, Piece)>,
+    // Move of the rook in a castling
+    castling_rook: Option<Move>,
+    // Player, castling rights, last move and hash before the move
+    data: GameMobilityData,
+}
+
+impl MoveUndo {
+    pub fn is_capture(&self) -> bool {
+        self.captured.is_some()
+    }
+
+    /// Captures and pawn moves reset the fifty-move rule.
+    pub fn is_zeroing(&self) -> bool {
+        self.is_capture() || self.moved_piece.piece == PieceType::Pawn
+    }
+}
+
 pub trait CastlingRights {
     fn can_castle_kingside(&self, player: Player) -> bool;
     fn can_castle_queenside(&self, player: Player) -> bool;
@@ -253,7 +292,7 @@ impl Default for GameInfo {
     }
 }
 
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Copy, Clone, Debug, PartialEq)]
 pub struct GameMobilityData {
     player: Player,
     last_move: Option<MoveInfo>,
@@ -295,6 +334,12 @@ impl<B: Board> From<B> for GameState<B> {
     }
 }
 
+impl<B: Board> Default for GameState<B> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl<B: Board> GameState<B> {
     pub const fn new() -> GameState<B> {
         GameState {
@@ -319,7 +364,7 @@ impl<B: Board> GameState<B> {
     }
 
     pub fn set_data(&mut self, data: &GameMobilityData) {
-        self.data = data.clone();
+        self.data = *data;
     }
 
     pub fn try_from_fen(fen: &[&str]) -> Option<Self> {
@@ -478,6 +523,69 @@ impl<B: Board> GameState<B> {
 
         hash
     }
+
+    // Hash of the position in the Polyglot opening book format, calculated on every call
+    pub fn polyglot_hash(&self) -> PolyglotHash {
+        let mut hash = PolyglotHash::from(&self.board);
+
+        if self.data.player == Player::White {
+            hash.switch_turn();
+        }
+
+        for player in [Player::White, Player::Black] {
+            if self.data.info.can_castle_kingside(player) {
+                hash.switch_kingside_castling(player);
+            }
+            if self.data.info.can_castle_queenside(player) {
+                hash.switch_queenside_castling(player);
+            }
+        }
+
+        // En passant is only hashed if a pawn can capture the passed pawn
+        if let Some(MoveInfo {
+            mv,
+            info: MoveExtraInfo::Passed,
+        }) = self.data.last_move
+        {
+            let capturing_pawn = Some(Piece {
+                piece: PieceType::Pawn,
+                player: self.data.player,
+            });
+            let can_capture = [mv.target.file.checked_sub(1), Some(mv.target.file + 1)]
+                .into_iter()
+                .flatten()
+                .filter(|file| *file < 8)
+                .any(|file| self.board.at(&pos!(mv.target.rank, file)) == capturing_pawn);
+
+            if can_capture {
+                hash.switch_en_passant_file(mv.target.file);
+            }
+        }
+
+        hash
+    }
+
+    // Hash of the position, only if it has already been calculated
+    pub fn cached_hash(&self) -> Option<GameHash> {
+        self.data.hash
+    }
+
+    // Pass the turn to the opponent without moving, as if the player could skip a move
+    pub fn do_null_move(&mut self) {
+        // En passant is no longer possible
+        if let Some(MoveInfo {
+            mv,
+            info: MoveExtraInfo::Passed,
+        }) = self.data.last_move
+        {
+            if let Some(hash) = self.data.hash.as_mut() {
+                hash.switch_en_passant_file(mv.source.file);
+            }
+        }
+
+        self.data.last_move = None;
+        self.update_player(!self.data.player);
+    }
 }
 
 impl<B: Board> ModifiableBoard<Position, Option<Piece>> for GameState<B> {
@@ -0,0 +1,193 @@
+use super::{Move, MoveAction, MoveActionType, MoveExtraInfo, MoveInfo, PromotionPieces};
+use crate::board::{Position, Square};
+
+use std::fmt;
+
+// Bits of the move, from the least significant one: source square and target square, of 6 bits
+// each, and 4 bits of flags
+const SQUARE_BITS: u16 = 6;
+const SQUARE_MASK: u16 = (1 << SQUARE_BITS) - 1;
+const FLAGS_OFFSET: u16 = 2 * SQUARE_BITS;
+
+// Flags of the extra information, and the promotion piece in the lowest 2 bits
+const OTHER_FLAG: u16 = 0;
+const PASSED_FLAG: u16 = 1;
+const EN_PASSANT_FLAG: u16 = 2;
+const CASTLE_KINGSIDE_FLAG: u16 = 3;
+const CASTLE_QUEENSIDE_FLAG: u16 = 4;
+const PROMOTION_FLAG: u16 = 8;
+
+fn square_bits(position: &Position) -> u16 {
+    Square::from(position).index() as u16
+}
+
+/// Move packed in 16 bits: source and target squares, and the extra information of the move.
+///
+/// The moves converted from a [`MoveAction`] only have the promotion piece, the rest of the
+/// extra information is only kept from a [`MoveInfo`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct PackedMove(u16);
+
+impl PackedMove {
+    pub fn new(source: &Position, target: &Position, info: MoveExtraInfo) -> Self {
+        let flags = match info {
+            MoveExtraInfo::Other => OTHER_FLAG,
+            MoveExtraInfo::Passed => PASSED_FLAG,
+            MoveExtraInfo::EnPassant => EN_PASSANT_FLAG,
+            MoveExtraInfo::CastleKingside => CASTLE_KINGSIDE_FLAG,
+            MoveExtraInfo::CastleQueenside => CASTLE_QUEENSIDE_FLAG,
+            MoveExtraInfo::Promotion(piece) => {
+                PROMOTION_FLAG
+                    | match piece {
+                        PromotionPieces::Knight => 0,
+                        PromotionPieces::Bishop => 1,
+                        PromotionPieces::Rook => 2,
+                        PromotionPieces::Queen => 3,
+                    }
+            }
+        };
+
+        PackedMove(square_bits(source) | square_bits(target) << SQUARE_BITS | flags << FLAGS_OFFSET)
+    }
+
+    pub fn source(&self) -> Position {
+        self.source_square().into()
+    }
+
+    pub fn target(&self) -> Position {
+        self.target_square().into()
+    }
+
+    pub fn source_square(&self) -> Square {
+        Square::from_index(usize::from(self.0 & SQUARE_MASK))
+    }
+
+    pub fn target_square(&self) -> Square {
+        Square::from_index(usize::from((self.0 >> SQUARE_BITS) & SQUARE_MASK))
+    }
+
+    pub fn info(&self) -> MoveExtraInfo {
+        match self.0 >> FLAGS_OFFSET {
+            PASSED_FLAG => MoveExtraInfo::Passed,
+            EN_PASSANT_FLAG => MoveExtraInfo::EnPassant,
+            CASTLE_KINGSIDE_FLAG => MoveExtraInfo::CastleKingside,
+            CASTLE_QUEENSIDE_FLAG => MoveExtraInfo::CastleQueenside,
+            flags if flags & PROMOTION_FLAG != 0 => MoveExtraInfo::Promotion(match flags & 0x3 {
+                0 => PromotionPieces::Knight,
+                1 => PromotionPieces::Bishop,
+                2 => PromotionPieces::Rook,
+                _ => PromotionPieces::Queen,
+            }),
+            _ => MoveExtraInfo::Other,
+        }
+    }
+
+    pub fn promotion(&self) -> Option<PromotionPieces> {
+        match self.info() {
+            MoveExtraInfo::Promotion(piece) => Some(piece),
+            _ => None,
+        }
+    }
+}
+
+impl From<MoveAction> for PackedMove {
+    fn from(move_action: MoveAction) -> Self {
+        let info = match move_action.move_type {
+            MoveActionType::Normal => MoveExtraInfo::Other,
+            MoveActionType::Promotion(piece) => MoveExtraInfo::Promotion(piece),
+        };
+        PackedMove::new(&move_action.mv.source, &move_action.mv.target, info)
+    }
+}
+
+impl From<PackedMove> for MoveAction {
+    fn from(mv: PackedMove) -> Self {
+        MoveAction {
+            mv: Move {
+                source: mv.source(),
+                target: mv.target(),
+            },
+            move_type: match mv.promotion() {
+                Some(piece) => MoveActionType::Promotion(piece),
+                None => MoveActionType::Normal,
+            },
+        }
+    }
+}
+
+impl From<MoveInfo> for PackedMove {
+    fn from(move_info: MoveInfo) -> Self {
+        PackedMove::new(&move_info.mv.source, &move_info.mv.target, move_info.info)
+    }
+}
+
+impl From<PackedMove> for MoveInfo {
+    fn from(mv: PackedMove) -> Self {
+        MoveInfo {
+            mv: Move {
+                source: mv.source(),
+                target: mv.target(),
+            },
+            info: mv.info(),
+        }
+    }
+}
+
+impl From<PackedMove> for u16 {
+    fn from(mv: PackedMove) -> Self {
+        mv.0
+    }
+}
+
+impl fmt::Display for PackedMove {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", MoveAction::from(*self).to_long_algebraic_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{mv, mva, pos};
+
+    #[test]
+    fn packed_move() {
+        assert_eq!(std::mem::size_of::<PackedMove>(), 2);
+
+        let move_actions = [
+            mva!(a1 => h8),
+            mva!(h8 => a1),
+            mva!(e2 => e4),
+            mva!(b7 => a8, PromotionPieces::Knight),
+            mva!(b7 => b8, PromotionPieces::Bishop),
+            mva!(g2 => h1, PromotionPieces::Rook),
+            mva!(g2 => g1, PromotionPieces::Queen),
+        ];
+        for move_action in move_actions {
+            let mv = PackedMove::from(move_action);
+            assert_eq!(MoveAction::from(mv), move_action);
+            assert_eq!(mv.to_string(), move_action.to_long_algebraic_string());
+        }
+
+        let move_infos = [
+            (mv!(e2 => e4), MoveExtraInfo::Passed),
+            (mv!(e5 => d6), MoveExtraInfo::EnPassant),
+            (mv!(e1 => g1), MoveExtraInfo::CastleKingside),
+            (mv!(e8 => c8), MoveExtraInfo::CastleQueenside),
+            (
+                mv!(a7 => a8),
+                MoveExtraInfo::Promotion(PromotionPieces::Queen),
+            ),
+            (mv!(g1 => f3), MoveExtraInfo::Other),
+        ];
+        for (mv, info) in move_infos {
+            let move_info = MoveInfo { mv, info };
+            assert_eq!(MoveInfo::from(PackedMove::from(move_info)), move_info);
+        }
+
+        // The squares are kept
+        let packed = PackedMove::from(mva!(b7 => b8, PromotionPieces::Knight));
+        assert_eq!(packed.source_square(), Square::from(pos!(b7)));
+        assert_eq!(packed.target_square(), Square::from(pos!(b8)));
+    }
+}
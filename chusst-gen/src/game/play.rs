@@ -1,11 +1,12 @@
 use super::{
-    CastlingRights, GameInfo, GameState, MoveAction, MoveActionType, MoveExtraInfo, MoveInfo,
+    CastlingRights, GameInfo, GameState, Move, MoveAction, MoveActionType, MoveExtraInfo, MoveInfo,
+    MoveUndo,
 };
 use crate::board::{
     Board, Direction, IterableBoard, ModifiableBoard, Piece, PieceType, Player, Position,
     PositionIterator,
 };
-use crate::dir;
+use crate::{dir, mv};
 use anyhow::{bail, Result};
 
 pub trait ModifiableGame<B: Board>:
@@ -21,7 +22,11 @@ pub trait ModifiableGame<B: Board>:
 
     fn last_move(&self) -> &Option<MoveInfo>;
 
-    fn do_move_no_checks(&mut self, mv: &MoveAction) -> Result<()>;
+    // Returns the changes made by the move, to undo it in place instead of cloning the game
+    fn do_move_no_checks(&mut self, mv: &MoveAction) -> Result<MoveUndo>;
+
+    // Restores the game as it was before the move, which must be the last one done
+    fn undo_move(&mut self, undo: MoveUndo);
 }
 
 impl<B: Board> ModifiableGame<B> for GameState<B> {
@@ -52,8 +57,9 @@ impl<B: Board> ModifiableGame<B> for GameState<B> {
         &self.data.last_move
     }
 
-    fn do_move_no_checks(&mut self, move_action: &MoveAction) -> Result<()> {
+    fn do_move_no_checks(&mut self, move_action: &MoveAction) -> Result<MoveUndo> {
         let mv = &move_action.mv;
+        let data = self.data;
 
         let Some(source_square) = self.board.at(&mv.source) else {
             bail!("Move {} from empty square:\n{}", mv, self.board);
@@ -93,6 +99,13 @@ impl<B: Board> ModifiableGame<B> for GameState<B> {
         };
 
         let captured = self.board.at(&mv.target);
+        let mut undo = MoveUndo {
+            mv: *mv,
+            moved_piece: source_square,
+            captured: captured.map(|piece| (mv.target, piece)),
+            castling_rook: None,
+            data,
+        };
 
         self.move_piece(&mv.source, &mv.target);
 
@@ -110,6 +123,7 @@ impl<B: Board> ModifiableGame<B> for GameState<B> {
                     .only_enemy(player)
                     .next()
                     .unwrap();
+                undo.captured = self.board.at(&passed).map(|piece| (passed, piece));
                 self.update(&passed, None);
             }
             MoveExtraInfo::Promotion(promotion_piece) => {
@@ -125,11 +139,13 @@ impl<B: Board> ModifiableGame<B> for GameState<B> {
                 let rook_source = self.try_move(&mv.source, &dir!(0, 3)).next().unwrap();
                 let rook_target = self.try_move(&mv.source, &dir!(0, 1)).next().unwrap();
                 self.move_piece(&rook_source, &rook_target);
+                undo.castling_rook = Some(mv!(rook_source, rook_target));
             }
             MoveExtraInfo::CastleQueenside => {
                 let rook_source = self.try_move(&mv.source, &dir!(0, -4)).next().unwrap();
                 let rook_target = self.try_move(&mv.source, &dir!(0, -1)).next().unwrap();
                 self.move_piece(&rook_source, &rook_target);
+                undo.castling_rook = Some(mv!(rook_source, rook_target));
             }
             _ => (),
         }
@@ -146,7 +162,8 @@ impl<B: Board> ModifiableGame<B> for GameState<B> {
         }
 
         if let Some(captured) = captured {
-            if captured.piece == PieceType::Rook && mv.target.rank == B::home_rank(&captured.player) {
+            if captured.piece == PieceType::Rook && mv.target.rank == B::home_rank(&captured.player)
+            {
                 match mv.target.file {
                     0 => self.disable_castle_queenside(captured.player),
                     7 => self.disable_castle_kingside(captured.player),
@@ -169,7 +186,25 @@ impl<B: Board> ModifiableGame<B> for GameState<B> {
             info: move_info,
         });
 
-        Ok(())
+        Ok(undo)
+    }
+
+    fn undo_move(&mut self, undo: MoveUndo) {
+        let mv = &undo.mv;
+
+        // The hash is restored along with the rest of the data, so only the board is updated
+        self.board.update(&mv.target, None);
+        self.board.update(&mv.source, Some(undo.moved_piece));
+
+        if let Some((position, piece)) = undo.captured {
+            self.board.update(&position, Some(piece));
+        }
+
+        if let Some(rook_move) = undo.castling_rook {
+            self.board.move_piece(&rook_move.target, &rook_move.source);
+        }
+
+        self.data = undo.data;
     }
 }
 
@@ -1,3 +1,6 @@
+mod polyglot;
+
+use self::polyglot::POLYGLOT_RANDOM_TABLE;
 use crate::game::Position;
 use lazy_static::lazy_static;
 use rand::prelude::*;
@@ -5,10 +8,7 @@ use std::hash::{BuildHasher, Hasher};
 use std::ops::BitXorAssign;
 use std::{fmt, sync::Mutex};
 
-use crate::{
-    board::{Board, Piece, PieceType, Player},
-    pos,
-};
+use crate::board::{Board, Piece, PieceType, Player, Square};
 
 #[derive(Default, Copy, Clone, Debug, PartialEq, Eq, Hash)]
 pub struct ZobristHash(u64);
@@ -56,7 +56,7 @@ impl Hasher for ZobristHasher {
     }
 }
 
-#[derive(Default)]
+#[derive(Clone, Default)]
 pub struct ZobristHashBuilder;
 
 impl BuildHasher for ZobristHashBuilder {
@@ -136,18 +136,24 @@ impl<T> ByFile<T> {
     }
 }
 
-#[derive(Default)]
-struct ByPosition<T>([ByFile<T>; 8]);
+struct BySquare<T>([T; 64]);
+
+// Arrays only implement Default up to 32 elements
+impl<T: Default> Default for BySquare<T> {
+    fn default() -> Self {
+        Self(std::array::from_fn(|_| T::default()))
+    }
+}
 
-impl<T> ByPosition<T> {
-    fn at(&self, rank: usize, file: usize) -> &T {
-        self.0[rank].at(file)
+impl<T> BySquare<T> {
+    fn at(&self, square: Square) -> &T {
+        &self.0[square.index()]
     }
 }
 
 #[derive(Default)]
 struct RandomTable {
-    pieces: ByPosition<ByPlayer<PiecesHash>>,
+    pieces: BySquare<ByPlayer<PiecesHash>>,
     black_turn: RandomHash,
     castling: ByPlayer<CastlingRightsHash>,
     can_do_en_passant: ByFile<RandomHash>,
@@ -192,11 +198,13 @@ impl ZobristHash {
         old_piece: Option<Piece>,
         new_piece: Option<Piece>,
     ) {
+        let square = Square::from(position);
+
         // First remove the old piece
         if let Some(Piece { piece, player }) = old_piece {
             self.0 ^= RANDOM_TABLE
                 .pieces
-                .at(position.rank, position.file)
+                .at(square)
                 .by_player(player)
                 .by_piece(piece)
                 .0;
@@ -205,7 +213,7 @@ impl ZobristHash {
         if let Some(Piece { piece, player }) = new_piece {
             self.0 ^= RANDOM_TABLE
                 .pieces
-                .at(position.rank, position.file)
+                .at(square)
                 .by_player(player)
                 .by_piece(piece)
                 .0;
@@ -216,14 +224,14 @@ impl ZobristHash {
         // First remove the old piece
         self.0 ^= RANDOM_TABLE
             .pieces
-            .at(source.rank, source.file)
+            .at(source.into())
             .by_player(moved_piece.player)
             .by_piece(moved_piece.piece)
             .0;
         // Then add the new piece
         self.0 ^= RANDOM_TABLE
             .pieces
-            .at(target.rank, target.file)
+            .at(target.into())
             .by_player(moved_piece.player)
             .by_piece(moved_piece.piece)
             .0;
@@ -233,15 +241,90 @@ impl ZobristHash {
 impl<B: Board> From<&B> for ZobristHash {
     fn from(value: &B) -> Self {
         let mut hash = ZobristHash(0);
-        for rank in 0..8usize {
-            for file in 0..8usize {
-                if let Some(Piece { piece, player }) = value.at(&pos!(rank, file)) {
-                    hash ^= RANDOM_TABLE
-                        .pieces
-                        .at(rank, file)
-                        .by_player(player)
-                        .by_piece(piece);
-                }
+        for square in Square::all() {
+            if let Some(Piece { piece, player }) = value.at(&square.into()) {
+                hash ^= RANDOM_TABLE
+                    .pieces
+                    .at(square)
+                    .by_player(player)
+                    .by_piece(piece);
+            }
+        }
+
+        hash
+    }
+}
+
+/// Hash of a position in the Polyglot opening book format.
+///
+/// Unlike [`ZobristHash`], the random numbers are fixed by the format, so that the hashes match
+/// the keys of the books created by other programs.
+#[derive(Default, Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct PolyglotHash(u64);
+
+impl fmt::Display for PolyglotHash {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:016x}", self.0)
+    }
+}
+
+impl From<PolyglotHash> for u64 {
+    fn from(value: PolyglotHash) -> u64 {
+        value.0
+    }
+}
+
+impl From<u64> for PolyglotHash {
+    fn from(value: u64) -> Self {
+        Self(value)
+    }
+}
+
+// Offsets of each kind of random number in the Polyglot table
+const POLYGLOT_CASTLING_OFFSET: usize = 768;
+const POLYGLOT_EN_PASSANT_OFFSET: usize = 772;
+const POLYGLOT_TURN_OFFSET: usize = 780;
+
+impl PolyglotHash {
+    // Unlike the Zobrist hash, the turn is switched when white moves
+    pub fn switch_turn(&mut self) {
+        self.0 ^= POLYGLOT_RANDOM_TABLE[POLYGLOT_TURN_OFFSET];
+    }
+
+    pub fn switch_kingside_castling(&mut self, player: Player) {
+        self.0 ^= POLYGLOT_RANDOM_TABLE[POLYGLOT_CASTLING_OFFSET + 2 * player as usize];
+    }
+
+    pub fn switch_queenside_castling(&mut self, player: Player) {
+        self.0 ^= POLYGLOT_RANDOM_TABLE[POLYGLOT_CASTLING_OFFSET + 2 * player as usize + 1];
+    }
+
+    pub fn switch_en_passant_file(&mut self, file: usize) {
+        self.0 ^= POLYGLOT_RANDOM_TABLE[POLYGLOT_EN_PASSANT_OFFSET + file];
+    }
+
+    pub fn update_piece(&mut self, position: &Position, piece: Piece) {
+        // Pieces are ordered by type, black first
+        let kind = match piece.piece {
+            PieceType::Pawn => 0,
+            PieceType::Knight => 1,
+            PieceType::Bishop => 2,
+            PieceType::Rook => 3,
+            PieceType::Queen => 4,
+            PieceType::King => 5,
+        } * 2
+            + usize::from(piece.player == Player::White);
+
+        self.0 ^= POLYGLOT_RANDOM_TABLE[64 * kind + Square::from(position).index()];
+    }
+}
+
+impl<B: Board> From<&B> for PolyglotHash {
+    fn from(value: &B) -> Self {
+        let mut hash = PolyglotHash(0);
+        for position in Square::all().map(Position::from) {
+            if let Some(piece) = value.at(&position) {
+                hash.update_piece(&position, piece);
             }
         }
 
@@ -1,17 +1,23 @@
 use anyhow::Result;
 use chusst_gen::eval::{
     EngineFeedback, EngineFeedbackMessage, EngineMessage, Game, GameHistory, GameMove,
-    HasStopSignal,
+    HasStopSignal, SearchLimits, SearchOptions, TranspositionTable,
 };
-use chusst_gen::game::{BitboardGame, MoveAction};
+use chusst_gen::game::{BitboardGame, ModifiableGame, MoveAction};
 use tokio::sync::mpsc;
 
+use crate::time_control::{DeadlineStopSignal, HasPonderHitSignal, TimeControl};
+
 use std::fmt;
 use std::io::Write;
 
 #[derive(Clone)]
 pub struct GoCommand {
-    pub depth: u32,
+    pub limits: SearchLimits,
+    pub options: SearchOptions,
+    pub time_control: TimeControl,
+    // Search on the opponent's time until ponderhit or stop
+    pub ponder: bool,
 }
 
 #[derive(Clone)]
@@ -24,6 +30,8 @@ pub struct NewGameCommand {
 pub enum EngineCommand {
     NewGame(Box<NewGameCommand>), // boxed due to big size
     Go(GoCommand),
+    SetHashSize(usize),
+    PonderHit,
     Stop,
     Exit,
 }
@@ -53,6 +61,7 @@ impl fmt::Display for EngineResponse {
 struct EngineCommandReceiver<'a> {
     receiver: &'a mut mpsc::UnboundedReceiver<EngineCommand>,
     messages: Vec<EngineCommand>,
+    ponder_hit: bool,
 }
 
 impl<'a> HasStopSignal for EngineCommandReceiver<'a> {
@@ -60,6 +69,7 @@ impl<'a> HasStopSignal for EngineCommandReceiver<'a> {
         if let Ok(cmd) = self.receiver.try_recv() {
             match cmd {
                 EngineCommand::Stop => return true,
+                EngineCommand::PonderHit => self.ponder_hit = true,
                 _ => self.messages.push(cmd),
             }
         }
@@ -67,6 +77,12 @@ impl<'a> HasStopSignal for EngineCommandReceiver<'a> {
     }
 }
 
+impl<'a> HasPonderHitSignal for EngineCommandReceiver<'a> {
+    fn ponder_hit(&mut self) -> bool {
+        std::mem::take(&mut self.ponder_hit)
+    }
+}
+
 struct SenderWriter {
     sender: mpsc::UnboundedSender<EngineResponse>,
 }
@@ -78,7 +94,7 @@ impl std::io::Write for SenderWriter {
         let msg_len = msg.len();
         self.sender
             .send(EngineResponse::Log(msg))
-            .map_err(|err| std::io::Error::new(std::io::ErrorKind::Other, err))?;
+            .map_err(std::io::Error::other)?;
         Ok(msg_len)
     }
 
@@ -140,9 +156,11 @@ pub async fn engine_task(
     let mut communicator = BufferedSenderWriter::new(from_engine);
     let mut game = BitboardGame::new();
     let mut history = GameHistory::new();
+    let mut transposition_table = TranspositionTable::default();
     let mut command_receiver = EngineCommandReceiver {
         receiver: &mut to_engine,
         messages: Vec::new(),
+        ponder_hit: false,
     };
 
     if communicator.send(EngineResponse::Ready).is_err() {
@@ -157,6 +175,7 @@ pub async fn engine_task(
                 if let Some(new_game) = new_game_cmd.game {
                     game = new_game;
                     history.clear();
+                    transposition_table.clear();
                 }
                 for mv in new_game_cmd.moves {
                     if game.do_move(&mv).is_none() {
@@ -167,15 +186,28 @@ pub async fn engine_task(
                 }
             }
             Some(EngineCommand::Go(go_command)) => {
+                let budget = go_command.time_control.budget(game.player());
+                if let Some(budget) = budget {
+                    let _ = writeln!(communicator, "Time budget: {} ms", budget.as_millis());
+                }
+                command_receiver.ponder_hit = false;
+                let mut stop_signal =
+                    DeadlineStopSignal::new(budget, go_command.ponder, &mut command_receiver);
                 let best_move = game.get_best_move_with_logger(
-                    go_command.depth,
+                    &go_command.limits,
+                    &go_command.options,
                     &history,
-                    &mut command_receiver,
+                    &transposition_table,
+                    &mut stop_signal,
                     &mut communicator,
                 );
                 let _ignore_error = communicator.send(EngineResponse::BestBranch(Some(best_move)));
             }
-            Some(EngineCommand::Stop) => (),
+            Some(EngineCommand::SetHashSize(size_mb)) => {
+                transposition_table = TranspositionTable::new(size_mb);
+            }
+            // The search has already finished
+            Some(EngineCommand::PonderHit) | Some(EngineCommand::Stop) => (),
             Some(EngineCommand::Exit) => break,
             None => {
                 let _ = writeln!(communicator, "Broken command pipeline");
@@ -1,11 +1,13 @@
 mod duplex_thread;
 mod engine;
 mod stdin;
+mod time_control;
 
 use std::fmt;
 use std::fs::File;
 use std::io::Write;
-use std::time::Instant;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 use anyhow::Result;
 use duplex_thread::DuplexChannel;
@@ -13,10 +15,14 @@ use engine::{EngineCommand, EngineResponse, GoCommand, NewGameCommand};
 use mio::{Poll, Token, Waker};
 use rust_fsm::*;
 
-use chusst_gen::eval::GameMove;
+use chusst_gen::eval::{
+    EngineFeedbackMessage, GameMove, OpeningBook, SearchLimits, SearchOptions, Tablebase,
+    TranspositionTable,
+};
 use chusst_gen::game::{BitboardGame, MoveAction};
 
 use stdin::{stdin_task, StdinResponse};
+use time_control::TimeControl;
 
 use crate::duplex_thread::create_duplex_thread;
 use crate::engine::engine_task;
@@ -47,6 +53,7 @@ state_machine! {
     Initializing(CommandUci) => Ready [OutputCommandId],
     Ready => {
         CommandGo => Searching [EngineCommandGo],
+        CommandGoPonder => Pondering [EngineCommandGo],
         CommandPosition => Ready [EngineCommandPosition],
         // Additional commands
         CommandUciNewGame => Ready [EngineCommandNewGame],
@@ -61,6 +68,21 @@ state_machine! {
         // Additional commands
         CommandIsReady => Ready [OutputCommandReadyOk],
     },
+    Pondering => {
+        CommandPonderHit => Searching [EngineCommandPonderHit],
+        CommandStop => WaitingForResult [EngineCommandStop],
+        EngineInfo => Pondering [OutputCommandInfo],
+        // The best move cannot be sent until ponderhit or stop
+        EngineResult => PonderingFinished [SavePonderBestMove],
+        // Additional commands
+        CommandIsReady => Pondering [OutputCommandReadyOk],
+    },
+    PonderingFinished => {
+        CommandPonderHit => Ready [OutputSavedCommandBestMove],
+        CommandStop => Ready [OutputSavedCommandBestMove],
+        // Additional commands
+        CommandIsReady => PonderingFinished [OutputCommandReadyOk],
+    },
     WaitingForResult => {
         EngineInfo => WaitingForResult [OutputCommandInfo],
         EngineResult => Ready [OutputCommandBestMove],
@@ -78,6 +100,12 @@ enum ParsedInput {
 const SEARCH_DEPTH_DEFAULT: u32 = 4;
 const SEARCH_DEPTH_MIN: u32 = 2;
 const SEARCH_DEPTH_MAX: u32 = 5;
+const HASH_SIZE_MIN: usize = 1;
+const HASH_SIZE_MAX: usize = 1024;
+const THREADS_MIN: usize = 1;
+const THREADS_MAX: usize = 256;
+const MULTI_PV_MIN: usize = 1;
+const MULTI_PV_MAX: usize = 256;
 
 impl fmt::Display for UciProtocolState {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
@@ -85,6 +113,8 @@ impl fmt::Display for UciProtocolState {
             UciProtocolState::Initializing => "Initializing",
             UciProtocolState::Ready => "Ready",
             UciProtocolState::Searching => "Searching",
+            UciProtocolState::Pondering => "Pondering",
+            UciProtocolState::PonderingFinished => "PonderingFinished",
             UciProtocolState::WaitingForResult => "WaitingForResult",
         };
         write!(f, "{}", state)
@@ -177,6 +207,10 @@ async fn uci_loop(
     }
 
     let mut search_depth = 3;
+    let mut search_options = SearchOptions::default();
+    // The opening book is only used if OwnBook is enabled
+    let mut own_book = false;
+    let mut opening_book: Option<Arc<OpeningBook>> = None;
 
     log!("Starting engine");
 
@@ -193,6 +227,8 @@ async fn uci_loop(
     let mut uci_protocol: StateMachine<UciProtocol> = StateMachine::new();
 
     let mut last_best_move: Option<Option<GameMove>> = None;
+    // Principal variation of the current search, to tell the move to ponder on
+    let mut principal_variation: Vec<MoveAction> = Vec::new();
 
     loop {
         let input = tokio::select! {
@@ -212,7 +248,11 @@ async fn uci_loop(
                             "setoption" => UciProtocolInput::CommandSetOption,
                             "ucinewgame" => UciProtocolInput::CommandUciNewGame,
                             "position" => UciProtocolInput::CommandPosition,
+                            "go" if words.iter().any(|word| word == "ponder") => {
+                                UciProtocolInput::CommandGoPonder
+                            }
                             "go" => UciProtocolInput::CommandGo,
+                            "ponderhit" => UciProtocolInput::CommandPonderHit,
                             "stop" => UciProtocolInput::CommandStop,
                             // Exit
                             "quit" => break,
@@ -293,6 +333,28 @@ async fn uci_loop(
                     SEARCH_DEPTH_MIN,
                     SEARCH_DEPTH_MAX
                 );
+                write_command!(
+                    "option name Hash type spin default {} min {} max {}",
+                    TranspositionTable::DEFAULT_SIZE_MB,
+                    HASH_SIZE_MIN,
+                    HASH_SIZE_MAX
+                );
+                write_command!("option name Ponder type check default false");
+                write_command!(
+                    "option name MultiPV type spin default {} min {} max {}",
+                    SearchOptions::default().multi_pv,
+                    MULTI_PV_MIN,
+                    MULTI_PV_MAX
+                );
+                write_command!(
+                    "option name Threads type spin default {} min {} max {}",
+                    SearchOptions::default().threads,
+                    THREADS_MIN,
+                    THREADS_MAX
+                );
+                write_command!("option name SyzygyPath type string default <empty>");
+                write_command!("option name OwnBook type check default false");
+                write_command!("option name BookFile type string default <empty>");
                 write_command!("uciok");
             }
             (
@@ -316,6 +378,74 @@ async fn uci_loop(
                                 log!("Invalid SearchDepth value");
                             }
                         }
+                        "Hash" => match value.parse::<usize>() {
+                            Ok(size_mb @ HASH_SIZE_MIN..=HASH_SIZE_MAX) => {
+                                if engine_channel
+                                    .to_thread
+                                    .send(EngineCommand::SetHashSize(size_mb))
+                                    .is_err()
+                                {
+                                    log!("Error: could not send hash size to engine");
+                                    break;
+                                }
+                            }
+                            Ok(_) => log!("Hash value out of range"),
+                            Err(_) => log!("Invalid Hash value"),
+                        },
+                        // Pondering is requested with go ponder, nothing to do
+                        "Ponder" => (),
+                        "MultiPV" => match value.parse::<usize>() {
+                            Ok(multi_pv @ MULTI_PV_MIN..=MULTI_PV_MAX) => {
+                                search_options.multi_pv = multi_pv
+                            }
+                            Ok(_) => log!("MultiPV value out of range"),
+                            Err(_) => log!("Invalid MultiPV value"),
+                        },
+                        "Threads" => match value.parse::<usize>() {
+                            Ok(threads @ THREADS_MIN..=THREADS_MAX) => {
+                                search_options.threads = threads
+                            }
+                            Ok(_) => log!("Threads value out of range"),
+                            Err(_) => log!("Invalid Threads value"),
+                        },
+                        "SyzygyPath" => {
+                            // The path can contain spaces
+                            let path = words[4..].join(" ");
+                            if path == "<empty>" {
+                                search_options.tablebase = None;
+                            } else {
+                                match Tablebase::open(&path) {
+                                    Ok(tablebase) => {
+                                        log!(
+                                            "Opened {} tablebases of up to {} pieces",
+                                            tablebase.len(),
+                                            tablebase.max_pieces()
+                                        );
+                                        search_options.tablebase = Some(Arc::new(tablebase));
+                                    }
+                                    Err(err) => log!("Invalid SyzygyPath value: {}", err),
+                                }
+                            }
+                        }
+                        "OwnBook" => match value.parse::<bool>() {
+                            Ok(value) => own_book = value,
+                            Err(_) => log!("Invalid OwnBook value"),
+                        },
+                        "BookFile" => {
+                            // The path can contain spaces
+                            let path = words[4..].join(" ");
+                            if path == "<empty>" {
+                                opening_book = None;
+                            } else {
+                                match OpeningBook::open(&path) {
+                                    Ok(book) => {
+                                        log!("Found {} moves in the opening book", book.len());
+                                        opening_book = Some(Arc::new(book));
+                                    }
+                                    Err(err) => log!("Invalid BookFile value: {}", err),
+                                }
+                            }
+                        }
                         _ => log!("Unknown parameter"),
                     }
                 } else {
@@ -404,35 +534,109 @@ async fn uci_loop(
             (Some(UciProtocolOutput::EngineCommandGo), ParsedInput::UciStdInInput(words)) => {
                 // go infinite
                 //   Search until stop is received
-                // go wtime 300000 btime 300000 movestogo 40
+                // go wtime 300000 btime 300000 winc 0 binc 0 movestogo 40
                 //   Search with this amount of time
-                match words.get(1).map(String::as_str) {
-                    Some("infinite") => {
-                        if engine_channel
-                            .to_thread
-                            .send(EngineCommand::Go(GoCommand {
-                                depth: search_depth,
-                            }))
-                            .is_err()
-                        {
-                            log!("Error: could not send go command to engine");
-                            break;
+                // go movetime 1000
+                //   Search exactly this amount of time
+                // go depth 5 / go nodes 100000 / go mate 3
+                //   Limit the search depth (in plies), the searched nodes or search for a mate
+                // go searchmoves e2e4 d2d4
+                //   Only search these moves
+                let mut time_control = TimeControl::default();
+                let mut depth: Option<u32> = None;
+                let mut nodes: Option<u32> = None;
+                let mut mate: Option<u32> = None;
+                let mut ponder = false;
+                let mut infinite = false;
+                let mut search_moves: Vec<MoveAction> = Vec::new();
+                let mut param_iter = words.iter().skip(1).map(String::as_str).peekable();
+                while let Some(param) = param_iter.next() {
+                    match param {
+                        "infinite" => infinite = true,
+                        "ponder" => ponder = true,
+                        "wtime" => time_control.white_time = parse_millis(param_iter.next()),
+                        "btime" => time_control.black_time = parse_millis(param_iter.next()),
+                        "winc" => time_control.white_increment = parse_millis(param_iter.next()),
+                        "binc" => time_control.black_increment = parse_millis(param_iter.next()),
+                        "movetime" => time_control.move_time = parse_millis(param_iter.next()),
+                        "movestogo" => {
+                            time_control.moves_to_go = param_iter
+                                .next()
+                                .and_then(|value| value.parse::<u32>().ok())
                         }
-                    }
-                    _ => {
-                        log!("Error: Unknown go command");
-                        break;
+                        "depth" => depth = parse_count(param_iter.next()),
+                        "nodes" => nodes = parse_count(param_iter.next()),
+                        "mate" => mate = parse_count(param_iter.next()),
+                        "searchmoves" => {
+                            // The list of moves ends at the next parameter
+                            while let Some(mv) = param_iter
+                                .peek()
+                                .and_then(|mv_str| MoveAction::try_from_long_algebraic_str(mv_str))
+                            {
+                                search_moves.push(mv);
+                                param_iter.next();
+                            }
+                        }
+                        _ => log!("Unknown go parameter {}", param),
                     }
                 }
+                let limits = SearchLimits {
+                    depth: match (depth, mate) {
+                        // The search depth starts at 0
+                        (Some(plies), _) => plies.saturating_sub(1),
+                        // The depth is limited by the number of moves of the mate
+                        (None, Some(_)) => u32::MAX,
+                        // The search goes on until it is stopped or runs out of time
+                        (None, None) if infinite || time_control.is_limited() => u32::MAX,
+                        (None, None) => search_depth,
+                    },
+                    nodes,
+                    mate,
+                    search_moves,
+                };
+                if engine_channel
+                    .to_thread
+                    .send(EngineCommand::Go(GoCommand {
+                        limits,
+                        options: SearchOptions {
+                            book: opening_book.clone().filter(|_| own_book),
+                            ..search_options.clone()
+                        },
+                        time_control,
+                        ponder,
+                    }))
+                    .is_err()
+                {
+                    log!("Error: could not send go command to engine");
+                    break;
+                }
+                principal_variation.clear();
+            }
+            (Some(UciProtocolOutput::EngineCommandPonderHit), ParsedInput::UciStdInInput(_)) => {
+                if engine_channel
+                    .to_thread
+                    .send(EngineCommand::PonderHit)
+                    .is_err()
+                {
+                    log!("Error: could not send ponderhit command to engine");
+                    break;
+                }
             }
             (
                 Some(UciProtocolOutput::SaveBestMove),
                 ParsedInput::EngineMessage(EngineResponse::BestBranch(best_move_result)),
             ) => {
-                let best_move_str = move_to_uci_string(&best_move_result);
+                let best_move_str =
+                    best_move_to_uci_string(&best_move_result, &principal_variation);
                 write_command!("bestmove {}", best_move_str);
                 last_best_move = Some(best_move_result);
             }
+            (
+                Some(UciProtocolOutput::SavePonderBestMove),
+                ParsedInput::EngineMessage(EngineResponse::BestBranch(best_move_result)),
+            ) => {
+                last_best_move = Some(best_move_result);
+            }
             (Some(UciProtocolOutput::EngineCommandStop), ParsedInput::UciStdInInput(_)) => {
                 if engine_channel.to_thread.send(EngineCommand::Stop).is_err() {
                     log!("Error: could not send go command to engine");
@@ -444,7 +648,7 @@ async fn uci_loop(
                 ParsedInput::UciStdInInput(_),
             ) => {
                 if let Some(best_move) = &last_best_move {
-                    let best_move_str = move_to_uci_string(best_move);
+                    let best_move_str = best_move_to_uci_string(best_move, &principal_variation);
                     write_command!("bestmove {}", best_move_str);
                 }
             }
@@ -452,18 +656,18 @@ async fn uci_loop(
                 Some(UciProtocolOutput::OutputCommandInfo),
                 ParsedInput::EngineMessage(EngineResponse::Info(info)),
             ) => {
-                write_command!(
-                    "info depth {} nodes {} score cp {}",
-                    info.depth,
-                    info.nodes,
-                    info.score
-                );
+                write_command!("{}", info_to_uci_string(&info));
+                // Only the best line is used to ponder
+                if !info.principal_variation.is_empty() && info.multi_pv.unwrap_or(1) == 1 {
+                    principal_variation = info.principal_variation;
+                }
             }
             (
                 Some(UciProtocolOutput::OutputCommandBestMove),
                 ParsedInput::EngineMessage(EngineResponse::BestBranch(best_move_result)),
             ) => {
-                let best_move_str = move_to_uci_string(&best_move_result);
+                let best_move_str =
+                    best_move_to_uci_string(&best_move_result, &principal_variation);
                 write_command!("bestmove {}", best_move_str);
             }
 
@@ -480,13 +684,93 @@ async fn uci_loop(
 
 fn move_to_uci_string(mv: &Option<GameMove>) -> String {
     match mv {
-        Some(GameMove::Normal(best_move)) => {
-            format!("{}{}", best_move.mv.source, best_move.mv.target)
-        }
+        Some(GameMove::Normal(best_move)) => best_move.to_long_algebraic_string(),
         Some(GameMove::Mate(_)) | None => "0000".to_owned(),
     }
 }
 
+// Best move, followed by the expected reply of the opponent if it is known
+fn best_move_to_uci_string(
+    best_move: &Option<GameMove>,
+    principal_variation: &[MoveAction],
+) -> String {
+    let best_move_str = move_to_uci_string(best_move);
+
+    match (best_move, principal_variation) {
+        (Some(GameMove::Normal(mv)), [pv_move, ponder_move, ..]) if mv == pv_move => format!(
+            "{} ponder {}",
+            best_move_str,
+            ponder_move.to_long_algebraic_string()
+        ),
+        _ => best_move_str,
+    }
+}
+
+fn info_to_uci_string(info: &EngineFeedbackMessage) -> String {
+    let mut fields = Vec::new();
+
+    if let Some(depth) = info.depth {
+        fields.push(format!("depth {}", depth));
+    }
+
+    if let Some(multi_pv) = info.multi_pv {
+        fields.push(format!("multipv {}", multi_pv));
+    }
+
+    if let Some(selective_depth) = info.selective_depth {
+        fields.push(format!("seldepth {}", selective_depth));
+    }
+
+    let millis = info.time.as_millis();
+    fields.push(format!("time {}", millis));
+    fields.push(format!("nodes {}", info.nodes));
+    if let Some(nps) = (u128::from(info.nodes) * 1000).checked_div(millis) {
+        fields.push(format!("nps {}", nps));
+    }
+
+    match (info.mate, info.score) {
+        (Some(moves), _) => fields.push(format!("score mate {}", moves)),
+        (None, Some(score)) => fields.push(format!("score cp {}", score)),
+        (None, None) => (),
+    }
+
+    if let Some(hash_full) = info.hash_full {
+        fields.push(format!("hashfull {}", hash_full));
+    }
+
+    if let Some(current_move) = &info.current_move {
+        fields.push(format!(
+            "currmove {}",
+            current_move.to_long_algebraic_string()
+        ));
+    }
+
+    if let Some(move_number) = info.current_move_number {
+        fields.push(format!("currmovenumber {}", move_number));
+    }
+
+    if !info.principal_variation.is_empty() {
+        let moves = info
+            .principal_variation
+            .iter()
+            .map(MoveAction::to_long_algebraic_string)
+            .collect::<Vec<String>>();
+        fields.push(format!("pv {}", moves.join(" ")));
+    }
+
+    format!("info {}", fields.join(" "))
+}
+
+fn parse_millis(value: Option<&str>) -> Option<Duration> {
+    // Clocks can be negative if the time has run out
+    let millis = value?.parse::<i64>().ok()?;
+    Some(Duration::from_millis(millis.max(0) as u64))
+}
+
+fn parse_count(value: Option<&str>) -> Option<u32> {
+    value?.parse::<u32>().ok().filter(|count| *count > 0)
+}
+
 fn parse_command(line: &str, logger: &mut Logger) -> Vec<String> {
     let trimmed_buffer = line.trim();
     let elapsed = logger.update();
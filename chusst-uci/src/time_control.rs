@@ -0,0 +1,164 @@
+use chusst_gen::board::Player;
+use chusst_gen::eval::HasStopSignal;
+
+use std::time::{Duration, Instant};
+
+// Moves expected until the end of the game when the GUI doesn't send movestogo
+const DEFAULT_MOVES_TO_GO: u32 = 30;
+// Time reserved for the communication with the GUI
+const MOVE_OVERHEAD: Duration = Duration::from_millis(50);
+
+/// Clock parameters of the `go` command, all of them optional.
+#[derive(Clone, Default)]
+pub struct TimeControl {
+    pub white_time: Option<Duration>,
+    pub black_time: Option<Duration>,
+    pub white_increment: Option<Duration>,
+    pub black_increment: Option<Duration>,
+    pub moves_to_go: Option<u32>,
+    pub move_time: Option<Duration>,
+}
+
+impl TimeControl {
+    /// Whether the `go` command limits the time of the search.
+    pub fn is_limited(&self) -> bool {
+        self.move_time.is_some() || self.white_time.is_some() || self.black_time.is_some()
+    }
+
+    /// Time that can be spent searching the next move of `player`, or `None` if the search is
+    /// not limited by time.
+    pub fn budget(&self, player: Player) -> Option<Duration> {
+        if let Some(move_time) = self.move_time {
+            return Some(move_time.saturating_sub(MOVE_OVERHEAD));
+        }
+
+        let (time, increment) = match player {
+            Player::White => (self.white_time?, self.white_increment.unwrap_or_default()),
+            Player::Black => (self.black_time?, self.black_increment.unwrap_or_default()),
+        };
+
+        let moves_to_go = self.moves_to_go.unwrap_or(DEFAULT_MOVES_TO_GO).max(1);
+        let available = time.saturating_sub(MOVE_OVERHEAD);
+
+        // Spread the remaining time among the remaining moves, and use most of the increment,
+        // but never more than half of the time left on the clock.
+        let budget = available / moves_to_go + increment * 3 / 4;
+
+        Some(budget.min(available / 2))
+    }
+}
+
+/// Stop signal that can also tell when the opponent has played the move the engine was
+/// pondering on.
+pub trait HasPonderHitSignal: HasStopSignal {
+    fn ponder_hit(&mut self) -> bool;
+}
+
+/// Stop signal that fires when the deadline is reached or when the inner signal fires.
+///
+/// While pondering there is no deadline, the clock starts when the ponder move is played.
+pub struct DeadlineStopSignal<'a, S: HasPonderHitSignal> {
+    budget: Option<Duration>,
+    deadline: Option<Instant>,
+    pondering: bool,
+    inner: &'a mut S,
+}
+
+impl<'a, S: HasPonderHitSignal> DeadlineStopSignal<'a, S> {
+    pub fn new(budget: Option<Duration>, pondering: bool, inner: &'a mut S) -> Self {
+        DeadlineStopSignal {
+            budget,
+            deadline: if pondering {
+                None
+            } else {
+                budget.map(|budget| Instant::now() + budget)
+            },
+            pondering,
+            inner,
+        }
+    }
+}
+
+impl<S: HasPonderHitSignal> HasStopSignal for DeadlineStopSignal<'_, S> {
+    fn stop(&mut self) -> bool {
+        if self.inner.stop() {
+            return true;
+        }
+
+        if self.pondering && self.inner.ponder_hit() {
+            self.pondering = false;
+            self.deadline = self.budget.map(|budget| Instant::now() + budget);
+        }
+
+        self.deadline
+            .is_some_and(|deadline| Instant::now() >= deadline)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn millis(millis: u64) -> Option<Duration> {
+        Some(Duration::from_millis(millis))
+    }
+
+    #[test]
+    fn budget() {
+        // Not limited by time
+        assert!(!TimeControl::default().is_limited());
+        assert_eq!(TimeControl::default().budget(Player::White), None);
+
+        // Fixed time per move, minus the overhead
+        let time_control = TimeControl {
+            move_time: millis(1000),
+            ..Default::default()
+        };
+        assert!(time_control.is_limited());
+        assert_eq!(time_control.budget(Player::Black), millis(950));
+
+        // Only the clock of the player to move counts
+        let time_control = TimeControl {
+            white_time: millis(60_050),
+            ..Default::default()
+        };
+        assert!(time_control.is_limited());
+        assert_eq!(time_control.budget(Player::White), millis(2000));
+        assert_eq!(time_control.budget(Player::Black), None);
+
+        // Most of the increment is added
+        let time_control = TimeControl {
+            black_time: millis(60_050),
+            black_increment: millis(1000),
+            white_increment: millis(5000),
+            ..Default::default()
+        };
+        assert_eq!(time_control.budget(Player::Black), millis(2750));
+
+        // The remaining time is spread among the moves to go
+        let time_control = TimeControl {
+            white_time: millis(60_050),
+            moves_to_go: Some(10),
+            ..Default::default()
+        };
+        assert_eq!(time_control.budget(Player::White), millis(6000));
+
+        // Never more than half of the time left, even for the last move
+        for moves_to_go in [0, 1] {
+            let time_control = TimeControl {
+                white_time: millis(10_050),
+                white_increment: millis(10_000),
+                moves_to_go: Some(moves_to_go),
+                ..Default::default()
+            };
+            assert_eq!(time_control.budget(Player::White), millis(5000));
+        }
+
+        // Less time than the overhead
+        let time_control = TimeControl {
+            white_time: millis(20),
+            ..Default::default()
+        };
+        assert_eq!(time_control.budget(Player::White), millis(0));
+    }
+}
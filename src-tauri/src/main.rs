@@ -2,7 +2,10 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
 use chusst_gen::board::{Piece, Position};
-use chusst_gen::eval::{self, Game, GameHistory};
+use chusst_gen::eval::{
+    self, Game, GameHistory, OpeningBook, SearchLimits, SearchOptions, StdoutFeedback,
+    TranspositionTable,
+};
 use chusst_gen::game::{Move, MoveAction, MoveActionType, PromotionPieces};
 
 #[cfg(feature = "bitboards")]
@@ -14,7 +17,7 @@ type GameModel = chusst_gen::game::SimpleGame;
 
 use serde::Serialize;
 
-use std::sync::Mutex;
+use std::sync::{Arc, Mutex};
 
 #[derive(Clone, Serialize)]
 struct MoveDescription {
@@ -36,14 +39,20 @@ struct GameData {
     move_history: GameHistory,
     /// The game history required by the UI
     history: Vec<TurnDescription>,
+    /// The opening book of the engine, if any
+    book: Option<Arc<OpeningBook>>,
 }
 
 static GAME: Mutex<GameData> = Mutex::new(GameData {
     game: GameModel::new(),
     move_history: Vec::new(),
     history: Vec::new(),
+    book: None,
 });
 
+// Environment variable with the path of a Polyglot opening book
+const BOOK_FILE_VAR: &str = "CHUSST_BOOK_FILE";
+
 // Learn more about Tauri commands at https://tauri.app/v1/guides/features/command
 #[tauri::command]
 fn get_game() -> GameModel {
@@ -122,25 +131,38 @@ fn do_move(
 
     game_data.move_history.push(mv);
 
-    let (black_move_opt, black_captures, mate) =
-        match game_data.game.get_best_move(&game_data.move_history, 4) {
-            eval::GameMove::Normal(mv) => {
-                let description = game_data.game.move_name(&mv).ok();
+    // The book moves are chosen randomly by their weight, so that the openings change
+    let options = SearchOptions {
+        book: game_data.book.clone(),
+        ..Default::default()
+    };
+    let best_move = game_data.game.get_best_move_with_logger(
+        &SearchLimits::from_depth(4),
+        &options,
+        &game_data.move_history,
+        &TranspositionTable::default(),
+        &mut (),
+        &mut StdoutFeedback::default(),
+    );
 
-                let black_captures = game_data.game.do_move(&mv);
-                assert!(black_captures.is_some());
+    let (black_move_opt, black_captures, mate) = match best_move {
+        eval::GameMove::Normal(mv) => {
+            let description = game_data.game.move_name(&mv).ok();
 
-                game_data.move_history.push(mv);
+            let black_captures = game_data.game.do_move(&mv);
+            assert!(black_captures.is_some());
 
-                let black_mate = game_data.game.is_mate();
+            game_data.move_history.push(mv);
 
-                (description, black_captures.unwrap(), black_mate)
-            }
-            eval::GameMove::Mate(mate) => match mate {
-                eval::MateType::Stalemate => (None, vec![], Some(eval::MateType::Stalemate)),
-                eval::MateType::Checkmate => (None, vec![], Some(eval::MateType::Checkmate)),
-            },
-        };
+            let black_mate = game_data.game.is_mate();
+
+            (description, black_captures.unwrap(), black_mate)
+        }
+        eval::GameMove::Mate(mate) => match mate {
+            eval::MateType::Stalemate => (None, vec![], Some(eval::MateType::Stalemate)),
+            eval::MateType::Checkmate => (None, vec![], Some(eval::MateType::Checkmate)),
+        },
+    };
 
     let history = &mut game_data.history;
     let turn = history.len() + 1;
@@ -191,6 +213,16 @@ fn restart() {
 }
 
 fn main() {
+    if let Ok(path) = std::env::var(BOOK_FILE_VAR) {
+        match OpeningBook::open(&path) {
+            Ok(book) => {
+                println!("Found {} moves in the opening book", book.len());
+                GAME.lock().unwrap().book = Some(Arc::new(book));
+            }
+            Err(err) => println!("Invalid opening book: {}", err),
+        }
+    }
+
     tauri::Builder::default()
         .invoke_handler(tauri::generate_handler![
             get_game,